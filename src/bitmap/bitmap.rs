@@ -1,4 +1,4 @@
-use std::mem;
+use std::fmt;
 use cgmath::Vector2;
 
 pub struct Bitmap<T> {
@@ -7,12 +7,17 @@ pub struct Bitmap<T> {
     height: u32,
 }
 
+impl<T> fmt::Debug for Bitmap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bitmap {{ width: {}, height: {} }}", self.width, self.height)
+    }
+}
+
 impl<T> Bitmap<T>
     where T: Default + Copy
 {
     pub fn new(width: u32, height: u32) -> Bitmap<T> {
-        let size = mem::size_of::<T>();
-        let len = (width * height * size as u32) as usize;
+        let len = (width * height) as usize;
         let mut buffer = Vec::with_capacity(len);
 
         for _ in 0..len {
@@ -40,6 +45,10 @@ impl<T> Bitmap<T>
         self.buffer.as_slice()
     }
 
+    pub fn slice_mut(&mut self) -> &mut [T] {
+        self.buffer.as_mut_slice()
+    }
+
     pub fn clear(&mut self, t: T) {
         for y in 0..self.height {
             for x in 0..self.width {
@@ -58,4 +67,16 @@ impl<T> Bitmap<T>
     	let offset = (point.y * self.width + point.x) as usize;
         self.buffer[offset]
     }
+
+    /// Nearest neighbor lookup with texture coordinates, wrapping outside of [0, 1]. `v` points
+    /// up, as in OBJ files, while rows are stored top to bottom.
+    pub fn sample(&self, uv: Vector2<f64>) -> T {
+        let u = uv.x - uv.x.floor();
+        let v = uv.y - uv.y.floor();
+
+        let x = ((u * self.width as f64) as u32).min(self.width - 1);
+        let y = (((1.0 - v) * self.height as f64) as u32).min(self.height - 1);
+
+        self.get_pixel(Vector2::new(x, y))
+    }
 }
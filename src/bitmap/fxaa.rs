@@ -0,0 +1,181 @@
+use cgmath::Vector2;
+
+use bitmap::Bitmap;
+use bitmap::pixel_format::Rgb24;
+use bitmap::scale;
+
+/// Edges with less contrast than this fraction of the brightest pixel around them are left
+/// alone, so that shading gradients don't get blurred.
+const EDGE_THRESHOLD: f64 = 1.0 / 8.0;
+/// Nor is anything darker than this, where steps are hard to see anyway.
+const EDGE_THRESHOLD_MIN: f64 = 1.0 / 16.0;
+/// How much single pixel features (a dot, or a line thinner than a pixel) are smeared into
+/// their surroundings; 0 keeps them sharp.
+const SUBPIXEL_QUALITY: f64 = 0.75;
+/// How far each step along an edge goes when searching for its ends, in pixels. The steps get
+/// longer the further the search goes, trading accuracy for speed on long edges.
+const SEARCH_STEPS: [f64; 12] = [1.0, 1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0, 8.0];
+
+/// Fast approximate antialiasing: finds edges by their contrast in brightness and blends each
+/// pixel on one with its neighbour across the edge, by how far along a stair step it is. Much
+/// cheaper than multisampling, but it only sees the finished image, so it softens texture
+/// detail a little and can't recover geometry thinner than a pixel.
+///
+/// This is Timothy Lottes' FXAA 3.11 quality algorithm, less the tuning presets. `src` and
+/// `dst` have to be the same size.
+pub fn fxaa_into(src: &Bitmap<Rgb24>, dst: &mut Bitmap<Rgb24>) {
+    let luma = luma_of(src);
+    let width = src.width() as i64;
+    let height = src.height() as i64;
+
+    for y in 0..height {
+        for x in 0..width {
+            let (x_offset, y_offset) = pixel_offset(&luma, x, y);
+            let color = if x_offset == 0.0 && y_offset == 0.0 {
+                src.get_pixel(Vector2::new(x as u32, y as u32))
+            } else {
+                scale::bilinear(src, x as f64 + x_offset, y as f64 + y_offset)
+            };
+            dst.set_pixel(Vector2::new(x as u32, y as u32), color);
+        }
+    }
+}
+
+/// Perceived brightness, in [0, 1].
+fn luma_of(src: &Bitmap<Rgb24>) -> Bitmap<f64> {
+    let mut luma = Bitmap::new(src.width(), src.height());
+
+    for (luma, color) in luma.slice_mut().iter_mut().zip(src.slice().iter()) {
+        let color = color.to_vec();
+        *luma = 0.299 * color.x + 0.587 * color.y + 0.114 * color.z;
+    }
+
+    luma
+}
+
+/// The luma at a pixel, clamped to the edges.
+fn luma_at(luma: &Bitmap<f64>, x: i64, y: i64) -> f64 {
+    let x = x.max(0).min(luma.width() as i64 - 1) as u32;
+    let y = y.max(0).min(luma.height() as i64 - 1) as u32;
+    luma.get_pixel(Vector2::new(x, y))
+}
+
+/// The luma between pixels, with pixel centers on whole numbers.
+fn luma_between(luma: &Bitmap<f64>, x: f64, y: f64) -> f64 {
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let tx = x - x0;
+    let ty = y - y0;
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let top = luma_at(luma, x0, y0) * (1.0 - tx) + luma_at(luma, x0 + 1, y0) * tx;
+    let bottom = luma_at(luma, x0, y0 + 1) * (1.0 - tx) + luma_at(luma, x0 + 1, y0 + 1) * tx;
+
+    top * (1.0 - ty) + bottom * ty
+}
+
+/// Where to resample the pixel at `(x, y)` from, relative to its center; nowhere if it isn't on
+/// an edge.
+fn pixel_offset(luma: &Bitmap<f64>, x: i64, y: i64) -> (f64, f64) {
+    let center = luma_at(luma, x, y);
+    let north = luma_at(luma, x, y - 1);
+    let south = luma_at(luma, x, y + 1);
+    let west = luma_at(luma, x - 1, y);
+    let east = luma_at(luma, x + 1, y);
+
+    let luma_min = center.min(north).min(south).min(west).min(east);
+    let luma_max = center.max(north).max(south).max(west).max(east);
+    let range = luma_max - luma_min;
+
+    if range < EDGE_THRESHOLD_MIN.max(luma_max * EDGE_THRESHOLD) {
+        return (0.0, 0.0);
+    }
+
+    let north_west = luma_at(luma, x - 1, y - 1);
+    let north_east = luma_at(luma, x + 1, y - 1);
+    let south_west = luma_at(luma, x - 1, y + 1);
+    let south_east = luma_at(luma, x + 1, y + 1);
+
+    // which way the edge runs, from how sharply the luma changes across rows and columns
+    let horizontal = (north_west + south_west - 2.0 * west).abs() +
+                     (north + south - 2.0 * center).abs() * 2.0 +
+                     (north_east + south_east - 2.0 * east).abs();
+    let vertical = (north_west + north_east - 2.0 * north).abs() +
+                   (west + east - 2.0 * center).abs() * 2.0 +
+                   (south_west + south_east - 2.0 * south).abs();
+    let is_horizontal = horizontal >= vertical;
+
+    // and which side of this pixel it's on
+    let (luma_negative, luma_positive) = if is_horizontal {
+        (north, south)
+    } else {
+        (west, east)
+    };
+    let gradient_negative = luma_negative - center;
+    let gradient_positive = luma_positive - center;
+    let negative_steepest = gradient_negative.abs() >= gradient_positive.abs();
+    let gradient_scaled = 0.25 * gradient_negative.abs().max(gradient_positive.abs());
+
+    // one pixel across the edge, towards the other side of it
+    let (step, luma_local_average) = if negative_steepest {
+        (-1.0, 0.5 * (luma_negative + center))
+    } else {
+        (1.0, 0.5 * (luma_positive + center))
+    };
+
+    // start halfway between this pixel and the one across the edge, and walk along the edge
+    // both ways until the luma there no longer looks like this edge's
+    let (along_x, along_y) = if is_horizontal { (1.0, 0.0) } else { (0.0, 1.0) };
+    let (start_x, start_y) = if is_horizontal {
+        (x as f64, y as f64 + step * 0.5)
+    } else {
+        (x as f64 + step * 0.5, y as f64)
+    };
+
+    let search = |direction: f64| {
+        let mut distance = 0.0;
+        let mut luma_end = 0.0;
+        for &length in SEARCH_STEPS.iter() {
+            distance += length;
+            luma_end = luma_between(luma,
+                                    start_x + along_x * distance * direction,
+                                    start_y + along_y * distance * direction) -
+                       luma_local_average;
+            if luma_end.abs() >= gradient_scaled {
+                break;
+            }
+        }
+        (distance, luma_end)
+    };
+    let (distance_negative, luma_end_negative) = search(-1.0);
+    let (distance_positive, luma_end_positive) = search(1.0);
+
+    // pixels near the end of a stair step are blended more than ones in its middle, and only
+    // if the end the pixel is nearest goes the right way for this pixel to be on the step
+    let (distance, luma_end) = if distance_negative < distance_positive {
+        (distance_negative, luma_end_negative)
+    } else {
+        (distance_positive, luma_end_positive)
+    };
+    let edge_length = distance_negative + distance_positive;
+    let center_smaller = center < luma_local_average;
+    let edge_offset = if (luma_end < 0.0) != center_smaller {
+        0.5 - distance / edge_length
+    } else {
+        0.0
+    };
+
+    // lone pixels that stand out from everything around them get blended anyway
+    let luma_average = (2.0 * (north + south + west + east) + north_west + north_east +
+                        south_west + south_east) / 12.0;
+    let subpixel = ((luma_average - center).abs() / range).max(0.0).min(1.0);
+    let subpixel = (-2.0 * subpixel + 3.0) * subpixel * subpixel;
+    let subpixel_offset = subpixel * subpixel * SUBPIXEL_QUALITY;
+
+    let offset = edge_offset.max(subpixel_offset) * step;
+    if is_horizontal {
+        (0.0, offset)
+    } else {
+        (offset, 0.0)
+    }
+}
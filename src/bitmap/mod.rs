@@ -1,4 +1,7 @@
 pub mod bitmap;
+pub mod fxaa;
 pub mod pixel_format;
+pub mod scale;
+pub mod tga;
 
 pub use bitmap::bitmap::Bitmap;
\ No newline at end of file
@@ -1,17 +1,47 @@
+#[cfg(feature = "window")]
 use sdl2::pixels::Color;
+use cgmath::Vector3;
+
 use math::Clamp;
 
 pub trait TransferToRgb {
     fn transfer(&self) -> (u8, u8, u8);
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rgb24 {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
+impl Rgb24 {
+    pub fn new(r: u8, g: u8, b: u8) -> Rgb24 {
+        Rgb24 { r: r, g: g, b: b }
+    }
+
+    /// As linear RGB in [0, 1].
+    pub fn to_vec(&self) -> Vector3<f64> {
+        Vector3::new(self.r as f64 / 255.0, self.g as f64 / 255.0, self.b as f64 / 255.0)
+    }
+}
+
+impl From<Vector3<f64>> for Rgb24 {
+    /// From RGB in [0, 1]; anything outside is clamped rather than wrapped.
+    fn from(color: Vector3<f64>) -> Self {
+        fn channel(value: f64) -> u8 {
+            (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+        }
+
+        Rgb24 {
+            r: channel(color.x),
+            g: channel(color.y),
+            b: channel(color.z),
+        }
+    }
+}
+
+#[cfg(feature = "window")]
 impl From<Color> for Rgb24 {
     fn from(color: Color) -> Self {
         let (r, g, b) = color.rgb();
@@ -33,7 +63,7 @@ impl TransferToRgb for Rgb24 {
 
 impl TransferToRgb for f64 {
     fn transfer(&self) -> (u8, u8, u8) {
-        let val = 255 - (((*self - 10.0) * 3.0).clamp(0.0, 1.0) * 255.0) as u8;
+        let val = 255 - (self.clamp(0.0, 1.0) * 255.0) as u8;
         (val, val, val)
     }
 }
@@ -0,0 +1,62 @@
+use cgmath::Vector2;
+
+use bitmap::Bitmap;
+use bitmap::pixel_format::Rgb24;
+
+/// How to fill in pixels when stretching a bitmap to a different size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Blocky, but cheap and exact at whole number ratios.
+    Nearest,
+    /// Blends the four closest source pixels.
+    Bilinear,
+}
+
+/// Stretches `src` over the whole of `dst`.
+pub fn scale_into(src: &Bitmap<Rgb24>, dst: &mut Bitmap<Rgb24>, filter: Filter) {
+    let x_ratio = src.width() as f64 / dst.width() as f64;
+    let y_ratio = src.height() as f64 / dst.height() as f64;
+
+    for y in 0..dst.height() {
+        for x in 0..dst.width() {
+            // sample at pixel centers so that both edges line up
+            let src_x = (x as f64 + 0.5) * x_ratio;
+            let src_y = (y as f64 + 0.5) * y_ratio;
+
+            let color = match filter {
+                Filter::Nearest => nearest(src, src_x, src_y),
+                Filter::Bilinear => bilinear(src, src_x - 0.5, src_y - 0.5),
+            };
+            dst.set_pixel(Vector2::new(x, y), color);
+        }
+    }
+}
+
+fn nearest(src: &Bitmap<Rgb24>, x: f64, y: f64) -> Rgb24 {
+    let x = (x as u32).min(src.width() - 1);
+    let y = (y as u32).min(src.height() - 1);
+    src.get_pixel(Vector2::new(x, y))
+}
+
+/// Blends the four pixels around `(x, y)`, with pixel centers on whole numbers.
+pub fn bilinear(src: &Bitmap<Rgb24>, x: f64, y: f64) -> Rgb24 {
+    let max_x = src.width() as i64 - 1;
+    let max_y = src.height() as i64 - 1;
+
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let tx = x - x0;
+    let ty = y - y0;
+
+    // clamped at the edges rather than wrapped, unlike texture sampling
+    let texel = |x: f64, y: f64| {
+        let x = (x as i64).max(0).min(max_x) as u32;
+        let y = (y as i64).max(0).min(max_y) as u32;
+        src.get_pixel(Vector2::new(x, y)).to_vec()
+    };
+
+    let top = texel(x0, y0) * (1.0 - tx) + texel(x0 + 1.0, y0) * tx;
+    let bottom = texel(x0, y0 + 1.0) * (1.0 - tx) + texel(x0 + 1.0, y0 + 1.0) * tx;
+
+    Rgb24::from(top * (1.0 - ty) + bottom * ty)
+}
@@ -0,0 +1,149 @@
+use std::cmp;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Result;
+use std::io::Error;
+use std::io::ErrorKind;
+
+use cgmath::Vector2;
+
+use bitmap::Bitmap;
+use bitmap::pixel_format::Rgb24;
+
+/// Reads a truecolor or greyscale TGA, raw or run-length encoded, like the textures that ship
+/// with the tinyrenderer models.
+pub fn import_file(filename: &str) -> Result<Bitmap<Rgb24>> {
+    let f = try!(File::open(filename));
+    let mut data = Vec::new();
+    try!(BufReader::new(f).read_to_end(&mut data));
+    read_tga(&data)
+}
+
+const HEADER_LEN: usize = 18;
+
+fn read_tga(data: &[u8]) -> Result<Bitmap<Rgb24>> {
+    if data.len() < HEADER_LEN {
+        return Err(invalid_data("truncated tga header"));
+    }
+
+    let id_length = data[0] as usize;
+    let color_map_type = data[1];
+    let image_type = data[2];
+    let width = data[12] as u32 | (data[13] as u32) << 8;
+    let height = data[14] as u32 | (data[15] as u32) << 8;
+    let depth = data[16];
+    let descriptor = data[17];
+
+    if color_map_type != 0 {
+        return Err(invalid_data("color mapped tga images are not supported"));
+    }
+
+    let bytes_per_pixel = match (image_type, depth) {
+        (2, 24) | (10, 24) => 3,
+        (2, 32) | (10, 32) => 4,
+        (3, 8) | (11, 8) => 1,
+        _ => return Err(invalid_data("unsupported tga image type")),
+    };
+    let rle = image_type >= 9;
+
+    if width == 0 || height == 0 {
+        return Err(invalid_data("empty tga image"));
+    }
+
+    let pixel_count = width as usize * height as usize;
+    let mut pos = HEADER_LEN + id_length;
+
+    // the header's size isn't to be trusted until there's data to back it up; run-length
+    // encoded images can hold more pixels than this, and grow as they're read
+    let stored_pixels = data.len().saturating_sub(pos) / bytes_per_pixel;
+    let mut pixels = Vec::with_capacity(cmp::min(pixel_count, stored_pixels));
+
+    while pixels.len() < pixel_count {
+        if rle {
+            if pos >= data.len() {
+                return Err(invalid_data("truncated tga data"));
+            }
+            let packet = data[pos];
+            pos += 1;
+
+            let run = (packet & 0x7f) as usize + 1;
+            if packet & 0x80 != 0 {
+                let pixel = try!(read_pixel(data, pos, bytes_per_pixel));
+                pos += bytes_per_pixel;
+                for _ in 0..run {
+                    pixels.push(pixel);
+                }
+            } else {
+                for _ in 0..run {
+                    pixels.push(try!(read_pixel(data, pos, bytes_per_pixel)));
+                    pos += bytes_per_pixel;
+                }
+            }
+        } else {
+            pixels.push(try!(read_pixel(data, pos, bytes_per_pixel)));
+            pos += bytes_per_pixel;
+        }
+    }
+
+    // rows are stored bottom to top unless the descriptor says otherwise
+    let top_to_bottom = descriptor & 0x20 != 0;
+
+    let mut bitmap = Bitmap::new(width, height);
+    for y in 0..height {
+        let row = if top_to_bottom { y } else { height - 1 - y };
+        for x in 0..width {
+            let pixel = pixels[y as usize * width as usize + x as usize];
+            bitmap.set_pixel(Vector2::new(x, row), pixel);
+        }
+    }
+
+    Ok(bitmap)
+}
+
+fn read_pixel(data: &[u8], pos: usize, bytes_per_pixel: usize) -> Result<Rgb24> {
+    if pos + bytes_per_pixel > data.len() {
+        return Err(invalid_data("truncated tga data"));
+    }
+
+    // truecolor pixels are stored as BGR(A)
+    Ok(match bytes_per_pixel {
+        1 => Rgb24::new(data[pos], data[pos], data[pos]),
+        _ => Rgb24::new(data[pos + 2], data[pos + 1], data[pos]),
+    })
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use bitmap::tga::read_tga;
+
+    /// An uncompressed 24 bit header for an image of the given size.
+    fn header(width: u16, height: u16) -> Vec<u8> {
+        let mut data = vec![0; 18];
+        data[2] = 2;
+        data[12] = width as u8;
+        data[13] = (width >> 8) as u8;
+        data[14] = height as u8;
+        data[15] = (height >> 8) as u8;
+        data[16] = 24;
+        data
+    }
+
+    #[test]
+    fn a_huge_header_without_data_is_invalid() {
+        let error = read_tga(&header(65535, 65535)).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn empty_images_are_invalid() {
+        assert_eq!(read_tga(&header(0, 4)).err().unwrap().kind(), ErrorKind::InvalidData);
+        assert_eq!(read_tga(&header(4, 0)).err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+}
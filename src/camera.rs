@@ -1,11 +1,103 @@
 extern crate cgmath;
 
 use cgmath::Point3;
+use cgmath::Vector3;
+use cgmath::Matrix4;
+use cgmath::Rad;
+
+/// How the camera flattens view space onto the screen. Both kinds map `near..far` in front of
+/// the camera to the depth range of the viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective {
+        /// Vertical field of view.
+        fovy: Rad<f64>,
+        /// Width over height.
+        aspect: f64,
+        near: f64,
+        far: f64,
+    },
+    /// Extents of the view volume in view space, centered on the camera's line of sight.
+    Orthographic {
+        left: f64,
+        right: f64,
+        bottom: f64,
+        top: f64,
+        near: f64,
+        far: f64,
+    },
+}
+
+impl Projection {
+    pub fn perspective<A>(fovy: A, aspect: f64, near: f64, far: f64) -> Projection
+        where A: Into<Rad<f64>>
+    {
+        Projection::Perspective {
+            fovy: fovy.into(),
+            aspect: aspect,
+            near: near,
+            far: far,
+        }
+    }
+
+    /// A box `height` units tall and `height * aspect` wide.
+    pub fn orthographic(height: f64, aspect: f64, near: f64, far: f64) -> Projection {
+        let half_height = height / 2.0;
+        let half_width = half_height * aspect;
+
+        Projection::Orthographic {
+            left: -half_width,
+            right: half_width,
+            bottom: -half_height,
+            top: half_height,
+            near: near,
+            far: far,
+        }
+    }
+
+    pub fn aspect(&self) -> f64 {
+        match *self {
+            Projection::Perspective { aspect, .. } => aspect,
+            Projection::Orthographic { left, right, bottom, top, .. } => {
+                (right - left) / (top - bottom)
+            }
+        }
+    }
+
+    /// Changes the width to match `aspect`, keeping the height and, for orthographic
+    /// projections, the center.
+    pub fn set_aspect(&mut self, new_aspect: f64) {
+        match *self {
+            Projection::Perspective { ref mut aspect, .. } => *aspect = new_aspect,
+            Projection::Orthographic { ref mut left, ref mut right, bottom, top, .. } => {
+                let center = (*left + *right) / 2.0;
+                let half_width = (top - bottom) / 2.0 * new_aspect;
+                *left = center - half_width;
+                *right = center + half_width;
+            }
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f64> {
+        match *self {
+            Projection::Perspective { fovy, aspect, near, far } => {
+                cgmath::perspective(fovy, aspect, near, far)
+            }
+            Projection::Orthographic { left, right, bottom, top, near, far } => {
+                cgmath::ortho(left, right, bottom, top, near, far)
+            }
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Camera {
     pub position: Point3<f64>,
     pub target: Point3<f64>,
+    /// Roughly which way is up on screen; it doesn't have to be perpendicular to the line of
+    /// sight, only not parallel to it.
+    pub up: Vector3<f64>,
+    pub projection: Projection,
 }
 
 impl Camera {
@@ -13,6 +105,31 @@ impl Camera {
         Camera {
             position: Point3::new(0.0, 0.0, 10.0),
             target: Point3::new(0.0, 0.0, 0.0),
+            up: Vector3::unit_y(),
+            projection: Projection::perspective(cgmath::deg(20.0), 1.0, 0.1, 100.0),
         }
     }
+
+    pub fn with_projection(self, projection: Projection) -> Camera {
+        Camera { projection: projection, ..self }
+    }
+
+    pub fn set_aspect(&mut self, aspect: f64) {
+        self.projection.set_aspect(aspect);
+    }
+
+    /// World space to view space.
+    pub fn view_matrix(&self) -> Matrix4<f64> {
+        Matrix4::look_at(self.position, self.target, self.up)
+    }
+
+    /// View space to clip space.
+    pub fn projection_matrix(&self) -> Matrix4<f64> {
+        self.projection.matrix()
+    }
+
+    /// World space to clip space.
+    pub fn view_projection_matrix(&self) -> Matrix4<f64> {
+        self.projection_matrix() * self.view_matrix()
+    }
 }
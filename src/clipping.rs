@@ -0,0 +1,197 @@
+use cgmath::Vector4;
+
+use shader::Varying;
+
+/// A vertex in homogeneous clip space along with the varyings the vertex shader gave it.
+#[derive(Clone, Copy)]
+pub struct ClipVertex<V> {
+    pub position: Vector4<f64>,
+    pub varying: V,
+}
+
+impl<V> ClipVertex<V>
+    where V: Varying
+{
+    pub fn new(position: Vector4<f64>, varying: V) -> ClipVertex<V> {
+        ClipVertex {
+            position: position,
+            varying: varying,
+        }
+    }
+
+    fn lerp(a: &ClipVertex<V>, b: &ClipVertex<V>, t: f64) -> ClipVertex<V> {
+        ClipVertex {
+            position: a.position + (b.position - a.position) * t,
+            varying: V::lerp(a.varying, b.varying, t),
+        }
+    }
+}
+
+/// How far out the guard band planes are, as a multiple of the view volume's half width and
+/// height. Anything inside them stays well within the range the rasterizer's fixed-point edge
+/// functions can handle, for viewports up to 16384 pixels across.
+const GUARD_BAND: f64 = 64.0;
+
+/// The planes of the canonical view volume, `-w <= x, y, z <= w`, and of the guard band
+/// around it, `-k * w <= x, y <= k * w`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClipPlane {
+    Near,
+    Far,
+    Left,
+    Right,
+    Bottom,
+    Top,
+    GuardLeft,
+    GuardRight,
+    GuardBottom,
+    GuardTop,
+}
+
+impl ClipPlane {
+    /// Signed distance from the plane, positive on the inside.
+    fn distance(&self, p: Vector4<f64>) -> f64 {
+        match *self {
+            ClipPlane::Near => p.w + p.z,
+            ClipPlane::Far => p.w - p.z,
+            ClipPlane::Left => p.w + p.x,
+            ClipPlane::Right => p.w - p.x,
+            ClipPlane::Bottom => p.w + p.y,
+            ClipPlane::Top => p.w - p.y,
+            ClipPlane::GuardLeft => GUARD_BAND * p.w + p.x,
+            ClipPlane::GuardRight => GUARD_BAND * p.w - p.x,
+            ClipPlane::GuardBottom => GUARD_BAND * p.w + p.y,
+            ClipPlane::GuardTop => GUARD_BAND * p.w - p.y,
+        }
+    }
+
+    fn bit(&self) -> u16 {
+        1 << (*self as u16)
+    }
+}
+
+/// Which planes a clip space position is outside of, one bit each. A triangle whose vertices
+/// share a bit is entirely outside that plane.
+pub fn clip_code(p: Vector4<f64>) -> u16 {
+    ALL_PLANES.iter()
+              .filter(|plane| plane.distance(p) < 0.0)
+              .fold(0, |code, plane| code | plane.bit())
+}
+
+// the guard band keeps the sides of huge triangles, which only the rasterizer's bounds would
+// otherwise cut down to size, from overflowing its edge functions
+const NEAR_FAR_PLANES: [ClipPlane; 6] = [ClipPlane::Near,
+                                         ClipPlane::Far,
+                                         ClipPlane::GuardLeft,
+                                         ClipPlane::GuardRight,
+                                         ClipPlane::GuardBottom,
+                                         ClipPlane::GuardTop];
+
+const FRUSTUM_PLANES: [ClipPlane; 6] = [ClipPlane::Near,
+                                        ClipPlane::Far,
+                                        ClipPlane::Left,
+                                        ClipPlane::Right,
+                                        ClipPlane::Bottom,
+                                        ClipPlane::Top];
+
+const ALL_PLANES: [ClipPlane; 10] = [ClipPlane::Near,
+                                     ClipPlane::Far,
+                                     ClipPlane::Left,
+                                     ClipPlane::Right,
+                                     ClipPlane::Bottom,
+                                     ClipPlane::Top,
+                                     ClipPlane::GuardLeft,
+                                     ClipPlane::GuardRight,
+                                     ClipPlane::GuardBottom,
+                                     ClipPlane::GuardTop];
+
+/// Which planes triangles get clipped against. Clipping to near and far is enough to keep
+/// geometry behind the camera off screen; the side planes are otherwise handled by the
+/// rasterizer's bounds, with triangles only clipped at the much wider guard band.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClipMode {
+    NearFar,
+    Frustum,
+}
+
+impl ClipMode {
+    pub fn planes(&self) -> &'static [ClipPlane] {
+        match *self {
+            ClipMode::NearFar => &NEAR_FAR_PLANES,
+            ClipMode::Frustum => &FRUSTUM_PLANES,
+        }
+    }
+
+    /// The bits of a `clip_code` that mean a triangle needs clipping.
+    pub fn mask(&self) -> u16 {
+        self.planes().iter().fold(0, |mask, plane| mask | plane.bit())
+    }
+}
+
+/// Clips a line segment, returning what's left of it, if anything.
+pub fn clip_line<V>(a: ClipVertex<V>,
+                    b: ClipVertex<V>,
+                    planes: &[ClipPlane])
+                    -> Option<(ClipVertex<V>, ClipVertex<V>)>
+    where V: Varying
+{
+    let (mut a, mut b) = (a, b);
+
+    for plane in planes {
+        let d_a = plane.distance(a.position);
+        let d_b = plane.distance(b.position);
+
+        if d_a < 0.0 && d_b < 0.0 {
+            return None;
+        }
+
+        let t = d_a / (d_a - d_b);
+        if d_a < 0.0 {
+            a = ClipVertex::lerp(&a, &b, t);
+        } else if d_b < 0.0 {
+            b = ClipVertex::lerp(&a, &b, t);
+        }
+    }
+
+    Some((a, b))
+}
+
+/// Sutherland–Hodgman clipping of a triangle. Returns the clipped polygon as a convex fan,
+/// which is empty if the triangle is entirely outside.
+pub fn clip_triangle<V>(a: ClipVertex<V>,
+                        b: ClipVertex<V>,
+                        c: ClipVertex<V>,
+                        planes: &[ClipPlane])
+                        -> Vec<ClipVertex<V>>
+    where V: Varying
+{
+    let mut polygon = vec![a, b, c];
+
+    for plane in planes {
+        if polygon.is_empty() {
+            break;
+        }
+
+        let input = polygon;
+        polygon = Vec::with_capacity(input.len() + 1);
+
+        for i in 0..input.len() {
+            let current = &input[i];
+            let next = &input[(i + 1) % input.len()];
+
+            let d_current = plane.distance(current.position);
+            let d_next = plane.distance(next.position);
+
+            if d_current >= 0.0 {
+                polygon.push(*current);
+            }
+
+            if (d_current >= 0.0) != (d_next >= 0.0) {
+                let t = d_current / (d_current - d_next);
+                polygon.push(ClipVertex::lerp(current, next, t));
+            }
+        }
+    }
+
+    polygon
+}
@@ -0,0 +1,221 @@
+extern crate cgmath;
+
+use std::f64::consts::PI;
+
+use cgmath::Vector;
+use cgmath::EuclideanVector;
+use cgmath::Vector3;
+use cgmath::Point3;
+
+use camera::Camera;
+use input::{InputEvent, Key, MouseButton};
+
+// stop just short of straight up or down, where the view's up vector degenerates
+const MAX_PITCH: f64 = PI / 2.0 - 0.01;
+
+/// Moves a camera around in response to input.
+pub trait CameraController {
+    fn handle_event(&mut self, event: &InputEvent);
+
+    /// Applies the input seen since the last update, `dt` seconds ago.
+    fn update(&mut self, camera: &mut Camera, dt: f64);
+}
+
+/// Circles the camera around a fixed point: drag with the left mouse button to turn, use the
+/// wheel to move closer or further away.
+#[derive(Debug)]
+pub struct OrbitController {
+    pub target: Point3<f64>,
+    pub distance: f64,
+    pub min_distance: f64,
+    pub max_distance: f64,
+    /// Radians per pixel dragged.
+    pub rotate_speed: f64,
+    /// Fraction of the distance covered per notch of the wheel.
+    pub zoom_speed: f64,
+    yaw: f64,
+    pitch: f64,
+    dragging: bool,
+    drag: (f64, f64),
+    zoom: f64,
+}
+
+impl OrbitController {
+    /// Starts from wherever the camera already is, orbiting its target.
+    pub fn from_camera(camera: &Camera) -> OrbitController {
+        let offset = camera.position - camera.target;
+        let distance = offset.length();
+
+        OrbitController {
+            target: camera.target,
+            distance: distance,
+            min_distance: 0.5,
+            max_distance: 50.0,
+            rotate_speed: 0.01,
+            zoom_speed: 0.1,
+            yaw: offset.x.atan2(offset.z),
+            pitch: (offset.y / distance).asin(),
+            dragging: false,
+            drag: (0.0, 0.0),
+            zoom: 0.0,
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn handle_event(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::MouseButtonDown { button: MouseButton::Left, .. } => self.dragging = true,
+            InputEvent::MouseButtonUp { button: MouseButton::Left, .. } => self.dragging = false,
+            InputEvent::MouseMotion { dx, dy, .. } if self.dragging => {
+                self.drag = (self.drag.0 + dx as f64, self.drag.1 + dy as f64);
+            }
+            InputEvent::MouseWheel { y, .. } => self.zoom += y as f64,
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, _: f64) {
+        // mouse deltas are already independent of the frame rate, so there's nothing to scale
+        self.yaw -= self.drag.0 * self.rotate_speed;
+        self.pitch = (self.pitch + self.drag.1 * self.rotate_speed)
+                         .max(-MAX_PITCH)
+                         .min(MAX_PITCH);
+        self.distance = (self.distance * (1.0 - self.zoom_speed).powf(self.zoom))
+                            .max(self.min_distance)
+                            .min(self.max_distance);
+        self.drag = (0.0, 0.0);
+        self.zoom = 0.0;
+
+        let offset = Vector3::new(self.pitch.cos() * self.yaw.sin(),
+                                  self.pitch.sin(),
+                                  self.pitch.cos() * self.yaw.cos());
+
+        camera.position = self.target + offset * self.distance;
+        camera.target = self.target;
+        camera.up = Vector3::unit_y();
+    }
+}
+
+/// Flies the camera freely: WASD to move, Q and E to sink and rise, shift to go faster, and
+/// drag with the right mouse button to look around.
+#[derive(Debug)]
+pub struct FlyController {
+    pub position: Point3<f64>,
+    /// Units per second.
+    pub move_speed: f64,
+    /// How much shift multiplies `move_speed` by.
+    pub boost: f64,
+    /// Radians per pixel dragged.
+    pub look_speed: f64,
+    yaw: f64,
+    pitch: f64,
+    looking: bool,
+    look: (f64, f64),
+    forward: bool,
+    back: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    fast: bool,
+}
+
+impl FlyController {
+    /// Starts from wherever the camera already is, looking the same way.
+    pub fn from_camera(camera: &Camera) -> FlyController {
+        let direction = (camera.target - camera.position).normalize();
+
+        FlyController {
+            position: camera.position,
+            move_speed: 2.0,
+            boost: 4.0,
+            look_speed: 0.005,
+            yaw: (-direction.x).atan2(-direction.z),
+            pitch: direction.y.asin(),
+            looking: false,
+            look: (0.0, 0.0),
+            forward: false,
+            back: false,
+            left: false,
+            right: false,
+            up: false,
+            down: false,
+            fast: false,
+        }
+    }
+
+    fn set_key(&mut self, key: Key, pressed: bool) {
+        match key {
+            Key::W => self.forward = pressed,
+            Key::S => self.back = pressed,
+            Key::A => self.left = pressed,
+            Key::D => self.right = pressed,
+            Key::E => self.up = pressed,
+            Key::Q => self.down = pressed,
+            Key::LShift | Key::RShift => self.fast = pressed,
+            _ => {}
+        }
+    }
+
+    fn direction(&self) -> Vector3<f64> {
+        Vector3::new(-self.pitch.cos() * self.yaw.sin(),
+                     self.pitch.sin(),
+                     -self.pitch.cos() * self.yaw.cos())
+    }
+}
+
+impl CameraController for FlyController {
+    fn handle_event(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::KeyDown { key, .. } => self.set_key(key, true),
+            InputEvent::KeyUp { key } => self.set_key(key, false),
+            InputEvent::MouseButtonDown { button: MouseButton::Right, .. } => self.looking = true,
+            InputEvent::MouseButtonUp { button: MouseButton::Right, .. } => self.looking = false,
+            InputEvent::FocusLost => {
+                self.forward = false;
+                self.back = false;
+                self.left = false;
+                self.right = false;
+                self.up = false;
+                self.down = false;
+                self.fast = false;
+                self.looking = false;
+            }
+            InputEvent::MouseMotion { dx, dy, .. } if self.looking => {
+                self.look = (self.look.0 + dx as f64, self.look.1 + dy as f64);
+            }
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f64) {
+        self.yaw -= self.look.0 * self.look_speed;
+        self.pitch = (self.pitch - self.look.1 * self.look_speed)
+                         .max(-MAX_PITCH)
+                         .min(MAX_PITCH);
+        self.look = (0.0, 0.0);
+
+        let forward = self.direction();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = Vector3::unit_y();
+
+        let mut movement = Vector3::zero();
+        if self.forward { movement = movement + forward; }
+        if self.back { movement = movement - forward; }
+        if self.right { movement = movement + right; }
+        if self.left { movement = movement - right; }
+        if self.up { movement = movement + up; }
+        if self.down { movement = movement - up; }
+
+        if movement.length2() > 0.0 {
+            let speed = if self.fast { self.move_speed * self.boost } else { self.move_speed };
+            // normalized so that going diagonally isn't faster
+            self.position = self.position + movement.normalize() * (speed * dt);
+        }
+
+        camera.position = self.position;
+        camera.target = self.position + forward;
+        camera.up = up;
+    }
+}
@@ -1,266 +1,1107 @@
-extern crate sdl2;
 extern crate cgmath;
+extern crate crossbeam;
+extern crate num_cpus;
 
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::f64;
+use std::mem;
+use std::sync::Mutex;
 
 use camera::Camera;
+use scene::Scene;
+use clipping;
+use clipping::{ClipMode, ClipVertex};
 use mesh::Mesh;
 use bitmap::Bitmap;
-use bitmap::pixel_format::Rgb24;
-use bitmap::pixel_format::TransferToRgb;
-
-use sdl2::pixels::Color;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::PixelFormatEnum;
-use sdl2::render::Renderer;
-use sdl2::render::Texture;
-use sdl2::EventPump;
+use bitmap::pixel_format::{Rgb24, TransferToRgb};
+use bitmap::fxaa;
+use bitmap::scale;
+use bitmap::scale::Filter;
+use presenter::Presenter;
+use raster;
+use raster::{Band, FillMode, Multisample, RasterizerState, ScreenVertex, Triangle, TILE_SIZE};
+use line;
+use shader::{Shader, Uniforms, VertexInput, Varying, DepthShader, FlatShader, GouraudShader,
+             PhongShader, TextureShader};
+use shader::lighting::LightSources;
+use shadow;
+use shadow::{ShadowMap, ShadowSettings};
 
 use cgmath::Vector2;
 use cgmath::Vector3;
 use cgmath::Matrix3;
 use cgmath::Matrix4;
-use cgmath::EuclideanVector;
-use cgmath::Vector;
+use cgmath::Matrix;
+use cgmath::SquareMatrix;
+use cgmath::Point;
 
 use rect::Point2i;
 use rect::Point3f;
 use rect::Rect;
+use viewport::Viewport;
 
-// #[derive(Debug)]
-pub struct Device<'a> {
-    // sdl_context: Sdl,
-    // video_subsystem: VideoSubsystem,
-    // window: Window,
-    renderer: Renderer<'a>,
-    texture: Texture,
+/// The rasterization core. Owns the color and depth buffers and knows nothing about where the
+/// finished frame ends up; hand it a `Presenter` to get the image out.
+///
+/// The buffers can be smaller than the frame that gets presented (see `set_render_scale`), in
+/// which case the image is stretched back up to the output size in `present`. With
+/// multisampling (see `set_multisample`) triangles are drawn into a buffer with several color
+/// samples per pixel, which `present` averages down into the back buffer first.
+pub struct Device {
     back_buffer: Bitmap<Rgb24>,
+    /// Has `multisample.samples()` entries per pixel, side by side.
     depth_buffer: Bitmap<f64>,
-    event_pump: EventPump,
+    multisample: Multisample,
+    /// Like `depth_buffer`, and only there while multisampling.
+    sample_buffer: Option<Bitmap<Rgb24>>,
+    viewport: Viewport,
+    clip_mode: ClipMode,
+    interpolation: Interpolation,
+    shading: ShadingMode,
+    rasterizer: RasterizerState,
+    stats: FrameStats,
+    output_size: (u32, u32),
+    render_scale: f64,
+    upscale_filter: Filter,
+    /// Only there while rendering at a different size than the output.
+    upscaled: Option<Bitmap<Rgb24>>,
+    /// Where the FXAA pass draws to, only there while it's on.
+    fxaa_buffer: Option<Bitmap<Rgb24>>,
+    shadows: Option<ShadowSettings>,
+    /// One for each light in the last scene rendered, if it casts shadows.
+    shadow_maps: Vec<Option<ShadowMap>>,
+    /// Which light's shadow map to draw over the corner of the frame.
+    shadow_map_overlay: Option<usize>,
+    /// Set while drawing a shadow map, when there's no color to write.
+    depth_only: bool,
+    threads: usize,
+}
+
+/// The lighting model `Device::render` uses. Flat and Gouraud shaded meshes with a diffuse map
+/// and texture coordinates are drawn with `TextureShader` instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadingMode {
+    /// One intensity per face.
+    Flat,
+    /// Lit at the vertices, interpolated across the face.
+    Gouraud,
+    /// Lit per pixel, with a reflection vector specular.
+    Phong,
+    /// Lit per pixel, with a half vector specular.
+    BlinnPhong,
 }
 
-#[derive(Debug)]
-pub enum EventPumpAction {
-    Quit,
-    Continue,
+/// Counts of what happened to the triangles of a frame. These are the triangles that come out
+/// of clipping, so a mesh face cut by the near plane can count as more than one, and a face
+/// entirely off screen as none.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    /// Filled; outlines and points aren't counted.
+    pub triangles_drawn: usize,
+    /// Dropped for facing the wrong way.
+    pub triangles_culled: usize,
 }
 
-impl<'a> Device<'a> {
-    pub fn new(title: &str, width: u32, height: u32) -> Device<'a> {
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
+/// A mesh vertex that's been through the vertex shader, shared by every face that uses it.
+#[derive(Clone, Copy)]
+struct ProcessedVertex<V> {
+    clip: ClipVertex<V>,
+    /// See `clipping::clip_code`.
+    clip_code: u16,
+    /// Only meaningful if the vertex is in front of the camera.
+    screen: ScreenVertex<V>,
+}
 
-        let window = video_subsystem.window(title, width, height)
-                                    .position_centered()
-                                    .build()
-                                    .unwrap();
+/// Everything drawn in one pass over the frame (or a shadow map), held back until the end so
+/// that the triangles of every mesh can be rasterized together.
+struct Batch<'a> {
+    draws: Vec<Box<QueuedDraw + 'a>>,
+    /// In window space, drawn over the top once everything's been filled.
+    lines: Vec<(Point3f, Point3f)>,
+    points: Vec<Point3f>,
+}
 
-        let renderer = window.renderer().build().unwrap();
+impl<'a> Batch<'a> {
+    fn new() -> Batch<'a> {
+        Batch {
+            draws: Vec::new(),
+            lines: Vec::new(),
+            points: Vec::new(),
+        }
+    }
+}
+
+/// A mesh's triangles, set up and waiting to be filled, with the shader's varyings hidden so
+/// that meshes drawn with different shaders can share a batch.
+trait QueuedDraw: Sync {
+    /// Fills the part of the triangles inside `clip`: every triangle, or only the ones binned
+    /// to tile number `tile` (see `raster::bin_triangles`).
+    fn draw(&self, band: &mut Band, clip: Rect, tile: Option<usize>);
+}
+
+struct MeshDraw<'a, S>
+    where S: Shader + 'a
+{
+    shader: &'a S,
+    uniforms: Uniforms<'a>,
+    interpolation: Interpolation,
+    triangles: Vec<Triangle<S::Varying>>,
+    /// Empty when drawing on a single thread, which doesn't need them.
+    bins: Vec<Vec<usize>>,
+}
+
+impl<'a, S> QueuedDraw for MeshDraw<'a, S>
+    where S: Shader
+{
+    fn draw(&self, band: &mut Band, clip: Rect, tile: Option<usize>) {
+        let mut draw = |triangle| {
+            raster::draw_triangle(band, clip, self.shader, &self.uniforms, self.interpolation,
+                                  triangle)
+        };
+
+        match tile {
+            Some(tile) => {
+                for &i in self.bins[tile].iter() {
+                    draw(&self.triangles[i]);
+                }
+            }
+            None => {
+                for triangle in self.triangles.iter() {
+                    draw(triangle);
+                }
+            }
+        }
+    }
+}
 
-        let texture = renderer.create_texture_streaming(PixelFormatEnum::RGB24, width, height)
-                              .unwrap();
+impl<V> ProcessedVertex<V>
+    where V: Copy
+{
+    fn with_varying(self, varying: V) -> ProcessedVertex<V> {
+        ProcessedVertex {
+            clip: ClipVertex { varying: varying, ..self.clip },
+            screen: ScreenVertex { varying: varying, ..self.screen },
+            ..self
+        }
+    }
+}
 
-        let event_pump = sdl_context.event_pump().unwrap();
+/// How varyings are interpolated across a triangle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    /// Correct for the perspective divide.
+    Perspective,
+    /// Plain screen space interpolation, as on the PlayStation.
+    Affine,
+}
 
+impl Device {
+    pub fn new(width: u32, height: u32) -> Device {
         Device {
-            renderer: renderer,
-            texture: texture,
             back_buffer: Bitmap::new(width, height),
             depth_buffer: Bitmap::new(width, height),
-            event_pump: event_pump,
+            multisample: Multisample::Off,
+            sample_buffer: None,
+            viewport: Viewport::new(0, 0, width, height),
+            clip_mode: ClipMode::NearFar,
+            interpolation: Interpolation::Perspective,
+            shading: ShadingMode::Flat,
+            rasterizer: RasterizerState::default(),
+            stats: FrameStats::default(),
+            output_size: (width, height),
+            render_scale: 1.0,
+            upscale_filter: Filter::Nearest,
+            upscaled: None,
+            fxaa_buffer: None,
+            shadows: None,
+            shadow_maps: Vec::new(),
+            shadow_map_overlay: None,
+            depth_only: false,
+            threads: num_cpus::get(),
         }
     }
 
-    pub fn poll_events(&mut self) -> EventPumpAction {
-        for event in self.event_pump.poll_iter() {
-            match event {
-                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    return EventPumpAction::Quit;
-                }
-                _ => {}
-            }
+    /// How many threads to rasterize with. One draws the whole frame on the calling thread;
+    /// any more split it into tiles, with exactly the same result. The threads are started
+    /// once per render (and once per shadow map), after every mesh has been set up.
+    pub fn set_threads(&mut self, threads: usize) {
+        assert!(threads > 0, "need at least one thread to draw with");
+        self.threads = threads;
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Changes the size of the presented frame, reallocating the buffers to match. The
+    /// viewport is reset to cover all of it.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.output_size = (width, height);
+        self.allocate_buffers();
+    }
+
+    pub fn output_size(&self) -> (u32, u32) {
+        self.output_size
+    }
+
+    /// Renders at `scale` times the output size and stretches the result to fit in `present`;
+    /// 0.5 draws a quarter of the pixels, for when the window is too big to keep up with. Like
+    /// `resize`, this resets the viewport.
+    pub fn set_render_scale(&mut self, scale: f64) {
+        assert!(scale > 0.0, "render scale must be positive");
+        self.render_scale = scale;
+        self.allocate_buffers();
+    }
+
+    pub fn render_scale(&self) -> f64 {
+        self.render_scale
+    }
+
+    pub fn set_upscale_filter(&mut self, filter: Filter) {
+        self.upscale_filter = filter;
+    }
+
+    pub fn upscale_filter(&self) -> Filter {
+        self.upscale_filter
+    }
+
+    fn allocate_buffers(&mut self) {
+        let (width, height) = self.output_size;
+        let render_width = ((width as f64 * self.render_scale).round() as u32).max(1);
+        let render_height = ((height as f64 * self.render_scale).round() as u32).max(1);
+
+        self.back_buffer = Bitmap::new(render_width, render_height);
+        self.viewport = Viewport::new(0, 0, render_width, render_height);
+        self.allocate_sample_buffers();
+
+        if self.fxaa_buffer.is_some() {
+            self.fxaa_buffer = Some(Bitmap::new(render_width, render_height));
         }
-        EventPumpAction::Continue
+
+        self.upscaled = if (render_width, render_height) == self.output_size {
+            None
+        } else {
+            Some(Bitmap::new(width, height))
+        };
     }
 
-    pub fn render(&mut self, cam: &Camera, meshes: Vec<&Mesh>) {
-        let view_mat = Matrix4::look_at(cam.position, cam.target, Vector3::unit_y());
+    /// Tests coverage and depth at several points in each pixel, which smooths out the edges
+    /// of triangles at the cost of a depth buffer (and a color buffer) that many times the size.
+    /// Fragments are still shaded once per pixel, so the insides of triangles cost the same.
+    pub fn set_multisample(&mut self, multisample: Multisample) {
+        self.multisample = multisample;
+        self.allocate_sample_buffers();
+    }
 
-        let projection_mat = cgmath::perspective(cgmath::rad(2.0),
-                                                 (self.back_buffer.width() as f64) /
-                                                 (self.back_buffer.height() as f64),
-                                                 0.01,
-                                                 1.0);
+    pub fn multisample(&self) -> Multisample {
+        self.multisample
+    }
 
-        for mesh in meshes {
-            let world_mat = Matrix4::from_translation(mesh.position) *
-                            Matrix4::from(Matrix3::from_euler(cgmath::rad(mesh.rotation.x),
-                                                              cgmath::rad(mesh.rotation.y),
-                                                              cgmath::rad(mesh.rotation.z)));
+    /// Smooths edges in `present` by post-processing the finished frame with FXAA (see
+    /// `bitmap::fxaa`). Much cheaper than multisampling in both time and memory, but blurrier.
+    /// It runs at the render resolution, before the frame is scaled up.
+    pub fn set_fxaa(&mut self, enabled: bool) {
+        self.fxaa_buffer = if enabled {
+            Some(Bitmap::new(self.back_buffer.width(), self.back_buffer.height()))
+        } else {
+            None
+        };
+    }
 
-            let mat = projection_mat * view_mat * world_mat;
+    pub fn fxaa(&self) -> bool {
+        self.fxaa_buffer.is_some()
+    }
 
-            let faces_count = mesh.faces.len();
+    /// Casts shadows from directional and spot lights, by rendering the scene's depth from
+    /// each of them before drawing it. Point lights don't cast any. `None` turns shadows off.
+    ///
+    /// Shadows are looked up per pixel, so `render` only draws them with `ShadingMode::Phong`
+    /// and `ShadingMode::BlinnPhong`; `render_with` hands them to any shader.
+    pub fn set_shadows(&mut self, shadows: Option<ShadowSettings>) {
+        self.shadows = shadows;
+    }
 
-            // println!("vertices = {:?}", mesh.vertices.len());
-            // println!("faces = {:?}", mesh.faces.len());
+    pub fn shadows(&self) -> Option<ShadowSettings> {
+        self.shadows
+    }
 
-            for (i, face) in mesh.faces.iter().enumerate() {
-                // let color = {
-                //     let color_val = 0.25 + (i % faces_count) as f64 * 0.75 / faces_count as f64;
-                //     let color_val_u8 = (color_val * 255.0) as u8;
-                //     Color::RGB(color_val_u8, 255 - color_val_u8, 0)
-                // };
+    /// The shadow maps of the last frame, one for each light that has one.
+    pub fn shadow_maps(&self) -> &[Option<ShadowMap>] {
+        &self.shadow_maps
+    }
 
-                let pixel_a = self.project(mesh.vertices[face.a], mat);
-                let pixel_b = self.project(mesh.vertices[face.b], mat);
-                let pixel_c = self.project(mesh.vertices[face.c], mat);
+    /// Shows the shadow map of the light with this index in the top left corner of the frame,
+    /// nearer surfaces brighter, for checking what the light can see and tuning the bias.
+    pub fn set_shadow_map_overlay(&mut self, light: Option<usize>) {
+        self.shadow_map_overlay = light;
+    }
 
-                // let p0 = Vector2::new(pixel_a.x, pixel_a.y).cast();
-                // let p1 = Vector2::new(pixel_b.x, pixel_b.y).cast();
-                // let p2 = Vector2::new(pixel_c.x, pixel_c.y).cast();
-                // self.draw_line(p0, p1, color);
-                // self.draw_line(p1, p2, color);
-                // self.draw_line(p2, p0, color);
+    pub fn shadow_map_overlay(&self) -> Option<usize> {
+        self.shadow_map_overlay
+    }
 
-                let normal = (mesh.vertices[face.c] - mesh.vertices[face.a])
-                    .cross(mesh.vertices[face.b] - mesh.vertices[face.a])
-                    .normalize();
+    fn allocate_sample_buffers(&mut self) {
+        let samples = self.multisample.samples() as u32;
+        let width = self.back_buffer.width() * samples;
+        let height = self.back_buffer.height();
 
-                let light_dir = Vector3::new(0.0, 0.0, -1.0).normalize();
+        self.depth_buffer = Bitmap::new(width, height);
+        self.sample_buffer = if samples > 1 {
+            Some(Bitmap::new(width, height))
+        } else {
+            None
+        };
+    }
 
-                let intensity = (normal.dot(light_dir) * 255.0) as u8;
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+    }
+
+    pub fn set_clip_mode(&mut self, clip_mode: ClipMode) {
+        self.clip_mode = clip_mode;
+    }
+
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
 
-                let color = Color::RGB(intensity, intensity, intensity);
+    pub fn set_shading(&mut self, shading: ShadingMode) {
+        self.shading = shading;
+    }
 
-                // self.draw_triangle(p0, p1, p2, color);
+    pub fn set_rasterizer_state(&mut self, rasterizer: RasterizerState) {
+        self.rasterizer = rasterizer;
+    }
+
+    pub fn rasterizer_state(&self) -> RasterizerState {
+        self.rasterizer
+    }
+
+    /// What's been drawn since the last `clear`.
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    pub fn back_buffer(&self) -> &Bitmap<Rgb24> {
+        &self.back_buffer
+    }
 
-                self.draw_triangle(pixel_a, pixel_b, pixel_c, color);
+    /// With multisampling, each row has every pixel's samples next to each other.
+    pub fn depth_buffer(&self) -> &Bitmap<f64> {
+        &self.depth_buffer
+    }
+
+    /// Draws every mesh in the scene through its active camera, with the built-in shader for
+    /// the current `ShadingMode`.
+    pub fn render(&mut self, scene: &Scene) {
+        let cam = scene.camera();
+        let shadow_maps = match self.shading {
+            ShadingMode::Phong | ShadingMode::BlinnPhong => self.render_shadow_maps(scene),
+            ShadingMode::Flat | ShadingMode::Gouraud => Vec::new(),
+        };
+        let lights = LightSources::new(scene.lights(), &shadow_maps);
+        // the shaders have to outlive the batch
+        let (flat, gouraud, texture) = (FlatShader, GouraudShader, TextureShader);
+        let phong = PhongShader::phong();
+        let blinn_phong = PhongShader::blinn_phong();
+        let mut batch = Batch::new();
+
+        for (world_mat, mesh) in scene.mesh_instances() {
+            let textured = mesh.material.diffuse_map.is_some() && !mesh.texcoords.is_empty();
+
+            match self.shading {
+                ShadingMode::Flat | ShadingMode::Gouraud if textured => {
+                    self.queue_mesh(&mut batch, cam, mesh, world_mat, lights, &texture)
+                }
+                ShadingMode::Flat => {
+                    self.queue_mesh(&mut batch, cam, mesh, world_mat, lights, &flat)
+                }
+                ShadingMode::Gouraud => {
+                    self.queue_mesh(&mut batch, cam, mesh, world_mat, lights, &gouraud)
+                }
+                ShadingMode::Phong => {
+                    self.queue_mesh(&mut batch, cam, mesh, world_mat, lights, &phong)
+                }
+                ShadingMode::BlinnPhong => {
+                    self.queue_mesh(&mut batch, cam, mesh, world_mat, lights, &blinn_phong)
+                }
             }
         }
+
+        self.draw_batch(batch);
+        self.shadow_maps = shadow_maps;
+    }
+
+    /// Draws every mesh in the scene with a custom shader.
+    pub fn render_with<S>(&mut self, scene: &Scene, shader: &S)
+        where S: Shader
+    {
+        let shadow_maps = self.render_shadow_maps(scene);
+        let lights = LightSources::new(scene.lights(), &shadow_maps);
+        let mut batch = Batch::new();
+
+        for (world_mat, mesh) in scene.mesh_instances() {
+            self.queue_mesh(&mut batch, scene.camera(), mesh, world_mat, lights, shader);
+        }
+
+        self.draw_batch(batch);
+        self.shadow_maps = shadow_maps;
     }
 
-    pub fn clear(&mut self, color: Color) {
-        self.back_buffer.clear(Rgb24::from(color));
+    /// Renders a shadow map for every light that can have one, if shadows are on, reusing the
+    /// last frame's buffers.
+    fn render_shadow_maps(&mut self, scene: &Scene) -> Vec<Option<ShadowMap>> {
+        let mut old_maps = mem::replace(&mut self.shadow_maps, Vec::new()).into_iter();
+        let settings = match self.shadows {
+            Some(settings) => settings,
+            None => return Vec::new(),
+        };
+
+        let instances = scene.mesh_instances();
+        let bounds = shadow::bounding_sphere(&instances);
+        let mut shadow_maps = Vec::with_capacity(scene.lights().len());
+
+        for light in scene.lights() {
+            let old_depth = old_maps.next().and_then(|map| map).map(ShadowMap::into_depth);
+            let camera = bounds.and_then(|(center, radius)| {
+                ShadowMap::light_camera(light, center, radius)
+            });
+
+            shadow_maps.push(camera.map(|camera| {
+                let mut depth = match old_depth {
+                    Some(depth) if (depth.width(), depth.height()) ==
+                                   (settings.size, settings.size) => depth,
+                    _ => Bitmap::new(settings.size, settings.size),
+                };
+                self.render_depth(&camera, &instances, &mut depth);
+                ShadowMap::new(camera, depth, settings)
+            }));
+        }
+
+        shadow_maps
+    }
+
+    /// Fills `depth` with the scene as seen through `camera`, using the same pipeline as the
+    /// frame but without touching its color or depth.
+    fn render_depth<'a>(&mut self,
+                        camera: &Camera,
+                        instances: &[(Matrix4<f64>, &'a Mesh)],
+                        depth: &mut Bitmap<f64>) {
+        mem::swap(&mut self.depth_buffer, depth);
+        let viewport = self.viewport;
+        let multisample = self.multisample;
+        let rasterizer = self.rasterizer;
+        let stats = self.stats;
+
+        self.viewport = Viewport::new(0, 0, self.depth_buffer.width(), self.depth_buffer.height());
+        self.multisample = Multisample::Off;
+        // both sides of every face, so that open meshes still cast shadows
+        self.rasterizer = RasterizerState::default();
+        self.depth_only = true;
         self.depth_buffer.clear(f64::MAX);
+
+        let mut batch = Batch::new();
+        for &(world_mat, mesh) in instances.iter() {
+            let lights = LightSources::unshadowed(&[]);
+            self.queue_mesh(&mut batch, camera, mesh, world_mat, lights, &DepthShader);
+        }
+        self.draw_batch(batch);
+
+        self.depth_only = false;
+        self.viewport = viewport;
+        self.multisample = multisample;
+        self.rasterizer = rasterizer;
+        self.stats = stats;
+        mem::swap(&mut self.depth_buffer, depth);
     }
 
-    fn copy_bitmap_to_texture<T>(src_bitmap: &Bitmap<T>, dest_texture: &mut Texture)
-        where T: Copy + Default + TransferToRgb
+    /// Takes a mesh through the geometry stages and adds what's left of it to `batch`.
+    fn queue_mesh<'a, S>(&mut self,
+                         batch: &mut Batch<'a>,
+                         cam: &Camera,
+                         mesh: &'a Mesh,
+                         world_mat: Matrix4<f64>,
+                         lights: LightSources<'a>,
+                         shader: &'a S)
+        where S: Shader
     {
-        let slice = src_bitmap.slice();
-        let width = src_bitmap.width();
-        let height = src_bitmap.height();
-
-        dest_texture.with_lock(None, |buffer: &mut [u8], _: usize| {
-                        for y in 0..height {
-                            for x in 0..width {
-                                let offset = (y * width + x) as usize;
-                                let woffset = offset * 3;
-                                let (r, g, b) = slice[offset].transfer();
-
-                                buffer[woffset] = r;
-                                buffer[woffset + 1] = g;
-                                buffer[woffset + 2] = b;
-                            }
-                        }
-                    })
-                    .unwrap();
+        let view_mat = cam.view_matrix();
+        let projection_mat = cam.projection_matrix();
+
+        let uniforms = Uniforms {
+            model: world_mat,
+            view: view_mat,
+            projection: projection_mat,
+            mvp: projection_mat * view_mat * world_mat,
+            normal_matrix: Device::normal_matrix(world_mat),
+            eye: cam.position.to_vec(),
+            light_sources: lights,
+            material: &mesh.material,
+        };
+
+        // println!("vertices = {:?}", mesh.vertices.len());
+        // println!("faces = {:?}", mesh.faces.len());
+
+        // vertex processing: every distinct corner (position, texture coordinates and normal)
+        // is shaded and projected once, however many faces share it
+        let mut processed = Vec::with_capacity(mesh.vertices.len());
+        let mut corner_indices = HashMap::new();
+        let mut faces = Vec::with_capacity(mesh.faces.len());
+        let lights_faces = shader.lights_faces();
+
+        for face in mesh.faces.iter() {
+            let positions = [face.a, face.b, face.c];
+            let normals = mesh.face_normals(face);
+            let uvs = mesh.face_texcoords(face);
+            let mut indices = [0; 3];
+
+            for i in 0..3 {
+                // without normals of their own, faces fall back on the face normal, which
+                // makes every corner different, unless the shader doesn't look at normals
+                // until it lights the whole face
+                let texcoord = face.texcoords.map(|t| t[i]);
+                let key = if lights_faces {
+                    Some((positions[i], texcoord, None))
+                } else {
+                    face.normals.map(|n| (positions[i], texcoord, Some(n[i])))
+                };
+                if let Some(&index) = key.and_then(|key| corner_indices.get(&key)) {
+                    indices[i] = index;
+                    continue;
+                }
+
+                let (position, varying) = shader.vertex(&uniforms, &VertexInput {
+                    position: mesh.vertices[positions[i]],
+                    normal: normals[i],
+                    uv: uvs[i],
+                });
+                let vertex = ClipVertex::new(position, varying);
+
+                processed.push(ProcessedVertex {
+                    clip: vertex,
+                    clip_code: clipping::clip_code(position),
+                    screen: self.project(&vertex),
+                });
+                indices[i] = processed.len() - 1;
+
+                if let Some(key) = key {
+                    corner_indices.insert(key, indices[i]);
+                }
+            }
+
+            faces.push(indices);
+        }
+
+        // primitive assembly
+        let target = self.target_bounds();
+        let clip_mask = self.clip_mode.mask();
+        let fill_mode = self.rasterizer.fill_mode;
+        let mut triangles = Vec::new();
+
+        // outlines of the faces that survive culling, for the fill modes that draw them; edges
+        // and vertices shared between unclipped faces are only drawn once
+        let lines = &mut batch.lines;
+        let points = &mut batch.points;
+        let mut drawn_edges = HashSet::new();
+        let mut drawn_vertices = HashSet::new();
+
+        for (face, indices) in mesh.faces.iter().zip(faces.iter()) {
+            let mut a = processed[indices[0]];
+            let mut b = processed[indices[1]];
+            let mut c = processed[indices[2]];
+
+            if a.clip_code & b.clip_code & c.clip_code != 0 {
+                // all on the wrong side of the same plane
+                continue;
+            }
+
+            if lights_faces {
+                let varying = shader.light_face(&uniforms, a.clip.varying, mesh.face_normal(face));
+                a = a.with_varying(varying);
+                b = b.with_varying(varying);
+                c = c.with_varying(varying);
+            }
+
+            if (a.clip_code | b.clip_code | c.clip_code) & clip_mask == 0 {
+                let triangle = Triangle::new(a.screen, b.screen, c.screen, target);
+                if !self.queue_triangle(&mut triangles, triangle) {
+                    continue;
+                }
+
+                for i in 0..3 {
+                    let (from, to) = (indices[i], indices[(i + 1) % 3]);
+                    let edge = (cmp::min(from, to), cmp::max(from, to));
+                    if fill_mode.outlines() && drawn_edges.insert(edge) {
+                        let from = processed[from].screen.position;
+                        lines.push((from, processed[to].screen.position));
+                    }
+                    if fill_mode == FillMode::Points && drawn_vertices.insert(from) {
+                        points.push(processed[from].screen.position);
+                    }
+                }
+                continue;
+            }
+
+            let polygon = clipping::clip_triangle(a.clip, b.clip, c.clip, self.clip_mode.planes());
+            let outline: Vec<_> = polygon.iter().map(|vertex| self.project(vertex)).collect();
+
+            // the clipped polygon is convex, so fan it out from its first vertex
+            let mut kept = false;
+            for i in 1..outline.len().saturating_sub(1) {
+                let triangle = Triangle::new(outline[0], outline[i], outline[i + 1], target);
+                kept |= self.queue_triangle(&mut triangles, triangle);
+            }
+
+            if kept {
+                for i in 0..outline.len() {
+                    let from = outline[i].position;
+                    let to = outline[(i + 1) % outline.len()].position;
+                    if fill_mode.outlines() {
+                        lines.push((from, to));
+                    }
+                    if fill_mode == FillMode::Points {
+                        points.push(from);
+                    }
+                }
+            }
+        }
+
+        if fill_mode.fills() {
+            self.stats.triangles_drawn += triangles.len();
+
+            let bins = if self.threads > 1 {
+                raster::bin_triangles(&triangles, self.width(), self.height())
+            } else {
+                Vec::new()
+            };
+
+            batch.draws.push(Box::new(MeshDraw {
+                shader: shader,
+                uniforms: uniforms,
+                interpolation: self.interpolation,
+                triangles: triangles,
+                bins: bins,
+            }));
+        }
+    }
+
+    /// Rasterizes everything in a batch, then draws its outlines over the top.
+    fn draw_batch(&mut self, batch: Batch) {
+        self.rasterize(&batch.draws);
+        self.draw_outlines(&batch.lines, &batch.points);
+    }
+
+    /// Draws lines and points, already in window space, over what's been rasterized.
+    fn draw_outlines(&mut self, lines: &[(Point3f, Point3f)], points: &[Point3f]) {
+        let target = self.target_bounds();
+        let color = self.rasterizer.line_color;
+        let antialiased = self.rasterizer.antialiased_lines;
+        let mut band = self.whole_band();
+
+        for &(from, to) in lines.iter() {
+            if antialiased {
+                line::draw_line_antialiased(&mut band, target, from, to, color);
+            } else {
+                line::draw_line(&mut band, target, from, to, color);
+            }
+        }
+
+        for &point in points.iter() {
+            line::draw_point(&mut band, target, point, color);
+        }
     }
 
-    pub fn present(&mut self) {
-        // Device::copy_bitmap_to_texture(&self.depth_buffer, &mut self.texture);
-        Device::copy_bitmap_to_texture(&self.back_buffer, &mut self.texture);
-        self.renderer.copy(&self.texture, None, None);
-        self.renderer.present();
+    /// Draws a line between two points in world space as seen through `camera`, depth tested
+    /// against everything drawn so far, in the current line color and style. For debugging
+    /// aids like normals, bounding boxes and light positions.
+    pub fn draw_debug_line(&mut self,
+                           camera: &Camera,
+                           from: Vector3<f64>,
+                           to: Vector3<f64>,
+                           color: Rgb24) {
+        let view_projection = camera.view_projection_matrix();
+        let from = ClipVertex::new(view_projection * from.extend(1.0), ());
+        let to = ClipVertex::new(view_projection * to.extend(1.0), ());
+
+        if let Some((from, to)) = clipping::clip_line(from, to, ClipMode::NearFar.planes()) {
+            let from = self.project(&from).position;
+            let to = self.project(&to).position;
+
+            let old_color = self.rasterizer.line_color;
+            self.rasterizer.line_color = color;
+            self.draw_outlines(&[(from, to)], &[]);
+            self.rasterizer.line_color = old_color;
+        }
     }
 
-    fn project(&self, vertex: Point3f, mat: Matrix4<f64>) -> Point3f {
-        let point = mat * vertex.extend(1.0);
+    /// The whole of the color and depth buffers, for drawing on the calling thread.
+    fn whole_band(&mut self) -> Band {
+        let width = self.width();
+        let color = match self.sample_buffer {
+            _ if self.depth_only => &mut [],
+            Some(ref mut sample_buffer) => sample_buffer.slice_mut(),
+            None => self.back_buffer.slice_mut(),
+        };
 
-        let width = self.width() as f64;
-        let height = self.height() as f64;
+        Band {
+            color: color,
+            depth: self.depth_buffer.slice_mut(),
+            top: 0,
+            width: width,
+            multisample: self.multisample,
+        }
+    }
 
-        Vector3::new(point.x * width + width / 2.0,
-                     -point.y * height + height / 2.0,
-                     point.z)
+    /// Keeps a triangle that made it through setup, unless it's facing the wrong way.
+    /// Returns whether it was kept.
+    fn queue_triangle<V>(&mut self,
+                         triangles: &mut Vec<Triangle<V>>,
+                         triangle: Option<Triangle<V>>)
+                         -> bool {
+        match triangle {
+            Some(triangle) => {
+                if self.rasterizer.culls(triangle.clockwise) {
+                    self.stats.triangles_culled += 1;
+                    false
+                } else {
+                    triangles.push(triangle);
+                    true
+                }
+            }
+            None => false,
+        }
     }
 
-    fn width(&self) -> i32 {
-        self.back_buffer.width() as i32
+    /// The inverse transpose of the world matrix's upper 3x3, which keeps normals
+    /// perpendicular to their surface under non-uniform scale.
+    fn normal_matrix(world_mat: Matrix4<f64>) -> Matrix3<f64> {
+        let linear = Matrix3::from_cols(world_mat.x.truncate(),
+                                        world_mat.y.truncate(),
+                                        world_mat.z.truncate());
+
+        // a node scaled flat has no inverse; its normals are meaningless anyway
+        linear.invert().map_or(linear, |inverse| inverse.transpose())
     }
 
-    fn height(&self) -> i32 {
-        self.back_buffer.height() as i32
+    /// Starts a new frame.
+    pub fn clear(&mut self, color: Rgb24) {
+        self.back_buffer.clear(color);
+        if let Some(ref mut sample_buffer) = self.sample_buffer {
+            sample_buffer.clear(color);
+        }
+        self.depth_buffer.clear(f64::MAX);
+        self.stats = FrameStats::default();
+    }
+
+    pub fn present<P>(&mut self, presenter: &mut P)
+        where P: Presenter
+    {
+        // presenter.present(&self.depth_buffer);
+        self.resolve();
+
+        if let Some(ref mut fxaa_buffer) = self.fxaa_buffer {
+            fxaa::fxaa_into(&self.back_buffer, fxaa_buffer);
+            mem::swap(&mut self.back_buffer, fxaa_buffer);
+        }
+        self.draw_shadow_map_overlay();
+
+        match self.upscaled {
+            Some(ref mut upscaled) => {
+                scale::scale_into(&self.back_buffer, upscaled, self.upscale_filter);
+                presenter.present(upscaled);
+            }
+            None => presenter.present(&self.back_buffer),
+        }
     }
 
-    fn set_pixel(&mut self, point: Point2i, z: f64, color: Color) {
-        if point.x < self.back_buffer.width() as i32
-            && point.x >= 0
-            && point.y < self.back_buffer.height() as i32
-            && point.y >= 0 {
+    fn draw_shadow_map_overlay(&mut self) {
+        let shadow_maps = &self.shadow_maps;
+        let shadow_map = match self.shadow_map_overlay.and_then(|i| shadow_maps.get(i)) {
+            Some(&Some(ref shadow_map)) => shadow_map.depth(),
+            _ => return,
+        };
+
+        let size = cmp::min(self.back_buffer.width(), self.back_buffer.height()) / 3;
+        for y in 0..size {
+            for x in 0..size {
+                let texel = Vector2::new(x * shadow_map.width() / size,
+                                         y * shadow_map.height() / size);
+                let (r, g, b) = shadow_map.get_pixel(texel).transfer();
+                self.back_buffer.set_pixel(Vector2::new(x, y), Rgb24::new(r, g, b));
+            }
+        }
+    }
 
-            let point2u = point.cast();
+    /// Averages each pixel's samples into the back buffer, if there are any.
+    fn resolve(&mut self) {
+        let samples = self.multisample.samples();
+        let sample_buffer = match self.sample_buffer {
+            Some(ref sample_buffer) => sample_buffer,
+            None => return,
+        };
 
-            if self.depth_buffer.get_pixel(point2u) < z {
-                return;
+        let pixels = self.back_buffer.slice_mut().iter_mut();
+        for (pixel, samples) in pixels.zip(sample_buffer.slice().chunks(samples)) {
+            let (mut r, mut g, mut b) = (0, 0, 0);
+            for sample in samples.iter() {
+                r += sample.r as usize;
+                g += sample.g as usize;
+                b += sample.b as usize;
             }
 
-            self.depth_buffer.set_pixel(point2u, z);
-            self.back_buffer.set_pixel(point2u, Rgb24::from(color));
+            let count = samples.len();
+            *pixel = Rgb24::new(((r + count / 2) / count) as u8,
+                                ((g + count / 2) / count) as u8,
+                                ((b + count / 2) / count) as u8);
         }
     }
 
-    fn draw_triangle(&mut self, pt0: Point3f, pt1: Point3f, pt2: Point3f, color: Color)
+    /// Clip space to window space: perspective divide, then the viewport transform.
+    fn project<V>(&self, vertex: &ClipVertex<V>) -> ScreenVertex<V>
+        where V: Varying
     {
-        let pti0 = pt0.truncate().cast();
-        let pti1 = pt1.truncate().cast();
-        let pti2 = pt2.truncate().cast();
-        let pts = vec![pti0, pti1, pti2];
-        let bounds = Rect::from_bounding(&pts);
+        let point = vertex.position;
+        let ndc = point.truncate() / point.w;
+
+        ScreenVertex {
+            position: self.viewport.transform(ndc),
+            inv_w: 1.0 / point.w,
+            varying: vertex.varying,
+        }
+    }
+
+    /// The size of what's being drawn to: the frame, or a shadow map in the shadow pass.
+    fn width(&self) -> i32 {
+        (self.depth_buffer.width() / self.multisample.samples() as u32) as i32
+    }
+
+    fn height(&self) -> i32 {
+        self.depth_buffer.height() as i32
+    }
+
+    /// The part of the render target that can be drawn to.
+    fn target_bounds(&self) -> Rect {
         let window_bounds = Rect::new(Point2i::new(0, 0), Point2i::new(self.width(), self.height()));
 
-        if let Some(clipped) = bounds.intersect(window_bounds) {
-            for y in clipped.top..clipped.bottom {
-                for x in clipped.left..clipped.right {
-                    let pt = Point2i::new(x, y);
+        // an empty rectangle if the viewport is entirely off the target
+        window_bounds.intersect(self.viewport.bounds())
+                     .unwrap_or(Rect::new(Point2i::new(0, 0), Point2i::new(0, 0)))
+    }
 
-                    // TODO better representation of a triangle??
-                    let bc = Device::barycentric(pt, pti0, pti1, pti2);
-                    if Device::is_inside_triangle(pt, bc) {
-                        let z = pt0.z * bc.0 + pt1.z * bc.1 + pt2.z * bc.2;
-                        self.set_pixel(pt, z, color);
-                    }
-                }
+    /// Fills the triangles of every draw in a batch, in order. With more than one thread the
+    /// frame is split into tiles, and each row of tiles is drawn by whichever thread gets to it
+    /// first; the threads are started once for the whole batch.
+    fn rasterize<'a>(&mut self, draws: &[Box<QueuedDraw + 'a>]) {
+        let width = self.width();
+        let height = self.height();
+
+        if draws.is_empty() {
+            return;
+        }
+
+        if self.threads <= 1 {
+            let mut band = self.whole_band();
+            let clip = Rect::new(Point2i::new(0, 0), Point2i::new(width, height));
+
+            for draw in draws.iter() {
+                draw.draw(&mut band, clip, None);
             }
+            return;
         }
+
+        let tiles_x = (width + TILE_SIZE - 1) / TILE_SIZE;
+        let multisample = self.multisample;
+        let band_len = (width * TILE_SIZE) as usize * multisample.samples();
+        let threads = self.threads;
+
+        let color = match self.sample_buffer {
+            _ if self.depth_only => &mut [],
+            Some(ref mut sample_buffer) => sample_buffer.slice_mut(),
+            None => self.back_buffer.slice_mut(),
+        };
+
+        // a depth only pass has no color to split up, so every band gets none
+        let mut colors = color.chunks_mut(band_len);
+        let bands: Vec<Band> = self.depth_buffer
+                                   .slice_mut()
+                                   .chunks_mut(band_len)
+                                   .enumerate()
+                                   .map(|(tile_y, depth)| {
+                                       Band {
+                                           color: colors.next().unwrap_or(&mut []),
+                                           depth: depth,
+                                           top: tile_y as i32 * TILE_SIZE,
+                                           width: width,
+                                           multisample: multisample,
+                                       }
+                                   })
+                                   .collect();
+        let queue = Mutex::new(bands.into_iter());
+
+        crossbeam::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    loop {
+                        // the lock is only held for as long as it takes to grab the next row
+                        let next = queue.lock().unwrap().next();
+                        let mut band = match next {
+                            Some(band) => band,
+                            None => break,
+                        };
+
+                        let tile_y = band.top / TILE_SIZE;
+                        for tile_x in 0..tiles_x {
+                            let tile = raster::tile_rect(tile_x, tile_y, width, height);
+                            let index = (tile_y * tiles_x + tile_x) as usize;
+                            for draw in draws.iter() {
+                                draw.draw(&mut band, tile, Some(index));
+                            }
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use cgmath::Vector3;
+    use cgmath::Vector4;
+    use cgmath::Point3;
+
+    use bitmap::pixel_format::Rgb24;
+    use camera::Camera;
+    use device::{Device, ShadingMode};
+    use light::Light;
+    use mesh::{Face, Mesh};
+    use scene::Scene;
+    use shader::{FlatShader, Fragment, Shader, Uniforms, VertexInput};
+
+    /// A wall just past the near plane, reaching millions of pixels off every side of the
+    /// frame.
+    fn wall_scene() -> Scene {
+        let mut camera = Camera::new();
+        camera.position = Point3::new(0.0, 0.0, 0.0);
+        camera.target = Point3::new(0.0, 0.0, -1.0);
+
+        let mut scene = Scene::new(camera);
+        let wall = Mesh::new("wall",
+                             vec![Vector3::new(-10000.0, -10000.0, -0.2),
+                                  Vector3::new(10000.0, -10000.0, -0.2),
+                                  Vector3::new(10000.0, 10000.0, -0.2),
+                                  Vector3::new(-10000.0, 10000.0, -0.2)],
+                             vec![Face::new(0, 1, 2), Face::new(0, 2, 3)]);
+        scene.add_mesh(wall, None);
+        scene
     }
 
-    // TODO move to "triangle" struct?
-    fn barycentric(pt: Point2i, pt0: Point2i, pt1: Point2i, pt2: Point2i) -> (f64, f64, f64) {
-        let u : Vector3<f64> = Vector3::new(
-            pt2.x - pt0.x,
-            pt1.x - pt0.x,
-            pt0.x - pt.x
-        ).cross(Vector3::new(
-            pt2.y - pt0.y,
-            pt1.y - pt0.y,
-            pt0.y - pt.y
-        )).cast();
-
-        if u.z.abs() < 1.0 {
-            // triangle is degenerate, in this case return smth with negative coordinates
-            (-1.0, 1.0, 1.0)
-        } else {
-            (1.0 - (u.x + u.y) / u.z,
-             u.y / u.z,
-             u.x / u.z)
+    /// Two overlapping meshes that cross several tiles, at an angle so that their depths cross.
+    fn overlapping_scene() -> Scene {
+        let mut camera = Camera::new();
+        camera.position = Point3::new(0.0, 0.0, 3.0);
+        camera.target = Point3::new(0.0, 0.0, 0.0);
+
+        let mut scene = Scene::new(camera);
+        let quad = Mesh::new("quad",
+                             vec![Vector3::new(-1.0, -1.0, -0.5),
+                                  Vector3::new(1.0, -1.0, 0.5),
+                                  Vector3::new(1.0, 1.0, 0.5),
+                                  Vector3::new(-1.0, 1.0, -0.5)],
+                             vec![Face::new(0, 1, 2), Face::new(0, 2, 3)]);
+        let triangle = Mesh::new("triangle",
+                                 vec![Vector3::new(-1.2, -0.3, 0.6),
+                                      Vector3::new(0.9, -1.1, -0.7),
+                                      Vector3::new(0.2, 1.3, 0.0)],
+                                 vec![Face::new(0, 1, 2)]);
+        scene.add_mesh(quad, None);
+        scene.add_mesh(triangle, None);
+        scene.add_light(Light::directional(Vector3::new(-0.3, -0.5, -1.0),
+                                           Vector3::new(1.0, 1.0, 1.0)));
+        scene
+    }
+
+    fn render_with_threads(threads: usize) -> Device {
+        let mut device = Device::new(100, 70);
+        device.set_threads(threads);
+        device.set_shading(ShadingMode::Gouraud);
+        device.clear(Rgb24::new(0, 0, 0));
+        device.render(&overlapping_scene());
+        device
+    }
+
+    #[test]
+    fn threads_draw_the_same_frame() {
+        let single = render_with_threads(1);
+        let tiled = render_with_threads(4);
+
+        assert!(single.back_buffer().slice() == tiled.back_buffer().slice());
+        assert!(single.depth_buffer().slice() == tiled.depth_buffer().slice());
+        assert!(single.depth_buffer().slice().iter().any(|&z| z < f64::MAX));
+    }
+
+    /// Flat shading that counts how many vertices it's been given.
+    struct CountingShader {
+        vertices: AtomicUsize,
+    }
+
+    impl Shader for CountingShader {
+        type Varying = <FlatShader as Shader>::Varying;
+
+        fn vertex(&self,
+                  uniforms: &Uniforms,
+                  input: &VertexInput)
+                  -> (Vector4<f64>, Self::Varying) {
+            self.vertices.fetch_add(1, Ordering::SeqCst);
+            FlatShader.vertex(uniforms, input)
+        }
+
+        fn fragment(&self, uniforms: &Uniforms, varying: Self::Varying) -> Fragment {
+            FlatShader.fragment(uniforms, varying)
+        }
+
+        fn lights_faces(&self) -> bool {
+            true
+        }
+
+        fn light_face(&self,
+                      uniforms: &Uniforms,
+                      provoking: Self::Varying,
+                      normal: Vector3<f64>)
+                      -> Self::Varying {
+            FlatShader.light_face(uniforms, provoking, normal)
         }
     }
 
-    ///! returns if a point pt is inside a triangle given by pt0, pt1, and pt2
-    fn is_inside_triangle(pt: Point2i, bc: (f64, f64, f64)) -> bool {
-        bc.0 >= 0.0 && bc.1 >= 0.0 && bc.2 >= 0.0
+    #[test]
+    fn flat_shading_transforms_each_vertex_once() {
+        let shader = CountingShader { vertices: AtomicUsize::new(0) };
+        let mut device = Device::new(100, 70);
+        device.clear(Rgb24::new(0, 0, 0));
+        device.render_with(&overlapping_scene(), &shader);
+
+        // the quad's four corners and the triangle's three
+        assert_eq!(shader.vertices.load(Ordering::SeqCst), 7);
+    }
+
+    #[test]
+    fn huge_triangles_near_the_camera_are_drawn() {
+        let mut device = Device::new(64, 64);
+        device.set_threads(1);
+        device.clear(Rgb24::new(0, 0, 0));
+        device.render(&wall_scene());
+
+        let depth = device.depth_buffer();
+        for (offset, &z) in depth.slice().iter().enumerate() {
+            assert!(z < f64::MAX, "({}, {}) wasn't drawn", offset % 64, offset / 64);
+        }
     }
 }
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// A key on the keyboard, named after what's printed on it in a US layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Escape, Space, Return, Tab, Backspace, Delete,
+    Up, Down, Left, Right,
+    LShift, RShift, LCtrl, RCtrl, LAlt, RAlt,
+    Minus, Equals, Comma, Period, Slash,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    X1,
+    X2,
+}
+
+/// Something the user did, translated out of whatever windowing library reported it. Mouse
+/// positions are in window pixels, from the top left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    /// The window was closed.
+    Quit,
+    KeyDown { key: Key, repeat: bool },
+    KeyUp { key: Key },
+    MouseMotion { x: i32, y: i32, dx: i32, dy: i32 },
+    MouseButtonDown { button: MouseButton, x: i32, y: i32 },
+    MouseButtonUp { button: MouseButton, x: i32, y: i32 },
+    /// Positive `y` is away from the user.
+    MouseWheel { x: i32, y: i32 },
+    Resized { width: u32, height: u32 },
+    FocusGained,
+    FocusLost,
+}
+
+/// What the keyboard and mouse look like right now, built up from the event stream. Call
+/// `begin_frame` before feeding it each frame's events so that the per-frame parts (presses,
+/// releases, deltas) start from nothing.
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys_down: HashSet<Key>,
+    keys_pressed: HashSet<Key>,
+    keys_released: HashSet<Key>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    mouse_position: (i32, i32),
+    mouse_delta: (i32, i32),
+    wheel_delta: (i32, i32),
+    quit_requested: bool,
+}
+
+impl InputState {
+    pub fn new() -> InputState {
+        InputState::default()
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.mouse_delta = (0, 0);
+        self.wheel_delta = (0, 0);
+    }
+
+    pub fn handle_event(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::Quit => self.quit_requested = true,
+            InputEvent::KeyDown { key, repeat } => {
+                if !repeat {
+                    self.keys_pressed.insert(key);
+                }
+                self.keys_down.insert(key);
+            }
+            InputEvent::KeyUp { key } => {
+                self.keys_down.remove(&key);
+                self.keys_released.insert(key);
+            }
+            InputEvent::MouseMotion { x, y, dx, dy } => {
+                self.mouse_position = (x, y);
+                self.mouse_delta = (self.mouse_delta.0 + dx, self.mouse_delta.1 + dy);
+            }
+            InputEvent::MouseButtonDown { button, x, y } => {
+                self.mouse_position = (x, y);
+                self.buttons_down.insert(button);
+                self.buttons_pressed.insert(button);
+            }
+            InputEvent::MouseButtonUp { button, x, y } => {
+                self.mouse_position = (x, y);
+                self.buttons_down.remove(&button);
+                self.buttons_released.insert(button);
+            }
+            InputEvent::MouseWheel { x, y } => {
+                self.wheel_delta = (self.wheel_delta.0 + x, self.wheel_delta.1 + y);
+            }
+            InputEvent::FocusLost => {
+                // the key and button releases are going to some other window now
+                self.keys_down.clear();
+                self.buttons_down.clear();
+            }
+            InputEvent::Resized { .. } | InputEvent::FocusGained => {}
+        }
+    }
+
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    /// Whether the key went down this frame. Key repeat doesn't count.
+    pub fn was_key_pressed(&self, key: Key) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    pub fn was_key_released(&self, key: Key) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn was_button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn was_button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    pub fn mouse_position(&self) -> (i32, i32) {
+        self.mouse_position
+    }
+
+    /// How far the mouse moved this frame.
+    pub fn mouse_delta(&self) -> (i32, i32) {
+        self.mouse_delta
+    }
+
+    /// How far the wheel turned this frame.
+    pub fn wheel_delta(&self) -> (i32, i32) {
+        self.wheel_delta
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+}
+
+/// A physical input an action can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    fn is_down(&self, input: &InputState) -> bool {
+        match *self {
+            Binding::Key(key) => input.is_key_down(key),
+            Binding::Mouse(button) => input.is_button_down(button),
+        }
+    }
+
+    fn was_pressed(&self, input: &InputState) -> bool {
+        match *self {
+            Binding::Key(key) => input.was_key_pressed(key),
+            Binding::Mouse(button) => input.was_button_pressed(button),
+        }
+    }
+}
+
+/// Maps the application's own actions to keys and buttons, so that code can ask about
+/// "jump" rather than the space bar and the bindings can be changed at runtime. An action can
+/// have any number of bindings; it's active if any of them is.
+#[derive(Debug)]
+pub struct ActionMap<A>
+    where A: Copy + Eq + Hash
+{
+    bindings: HashMap<A, Vec<Binding>>,
+}
+
+impl<A> ActionMap<A>
+    where A: Copy + Eq + Hash
+{
+    pub fn new() -> ActionMap<A> {
+        ActionMap { bindings: HashMap::new() }
+    }
+
+    pub fn bind(&mut self, action: A, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_insert_with(Vec::new);
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: A, binding: Binding) {
+        if let Some(bindings) = self.bindings.get_mut(&action) {
+            bindings.retain(|&b| b != binding);
+        }
+    }
+
+    /// Replaces all of an action's bindings with one.
+    pub fn rebind(&mut self, action: A, binding: Binding) {
+        self.bindings.insert(action, vec![binding]);
+    }
+
+    pub fn clear(&mut self, action: A) {
+        self.bindings.remove(&action);
+    }
+
+    pub fn bindings(&self, action: A) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |bindings| &bindings[..])
+    }
+
+    /// Whether any of the action's bindings is held.
+    pub fn is_active(&self, action: A, input: &InputState) -> bool {
+        self.bindings(action).iter().any(|binding| binding.is_down(input))
+    }
+
+    /// Whether any of the action's bindings went down this frame.
+    pub fn was_triggered(&self, action: A, input: &InputState) -> bool {
+        self.bindings(action).iter().any(|binding| binding.was_pressed(input))
+    }
+}
@@ -0,0 +1,127 @@
+use cgmath::Vector3;
+use cgmath::Rad;
+use cgmath::EuclideanVector;
+use cgmath::Vector;
+
+/// How a light falls off with distance: `1 / (constant + linear * d + quadratic * d^2)`.
+#[derive(Debug, Clone, Copy)]
+pub struct Attenuation {
+    pub constant: f64,
+    pub linear: f64,
+    pub quadratic: f64,
+}
+
+impl Attenuation {
+    pub fn new(constant: f64, linear: f64, quadratic: f64) -> Attenuation {
+        Attenuation {
+            constant: constant,
+            linear: linear,
+            quadratic: quadratic,
+        }
+    }
+
+    /// No falloff at all.
+    pub fn none() -> Attenuation {
+        Attenuation::new(1.0, 0.0, 0.0)
+    }
+
+    /// Physically based inverse square falloff.
+    pub fn inverse_square() -> Attenuation {
+        Attenuation::new(0.0, 0.0, 1.0)
+    }
+
+    fn factor(&self, distance: f64) -> f64 {
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+}
+
+/// A light source. Positions and directions are in world space and colors are linear RGB,
+/// premultiplied by the light's intensity.
+#[derive(Debug, Clone, Copy)]
+pub enum Light {
+    /// Infinitely far away, like the sun. `direction` is the way the light travels.
+    Directional {
+        direction: Vector3<f64>,
+        color: Vector3<f64>,
+    },
+    Point {
+        position: Vector3<f64>,
+        color: Vector3<f64>,
+        attenuation: Attenuation,
+    },
+    /// A point light restricted to a cone around `direction`, full strength within
+    /// `inner_angle` of it and fading out towards `outer_angle`.
+    Spot {
+        position: Vector3<f64>,
+        direction: Vector3<f64>,
+        color: Vector3<f64>,
+        attenuation: Attenuation,
+        inner_angle: Rad<f64>,
+        outer_angle: Rad<f64>,
+    },
+}
+
+impl Light {
+    pub fn directional(direction: Vector3<f64>, color: Vector3<f64>) -> Light {
+        Light::Directional {
+            direction: direction.normalize(),
+            color: color,
+        }
+    }
+
+    pub fn point(position: Vector3<f64>, color: Vector3<f64>, attenuation: Attenuation) -> Light {
+        Light::Point {
+            position: position,
+            color: color,
+            attenuation: attenuation,
+        }
+    }
+
+    pub fn spot(position: Vector3<f64>,
+                direction: Vector3<f64>,
+                color: Vector3<f64>,
+                attenuation: Attenuation,
+                inner_angle: Rad<f64>,
+                outer_angle: Rad<f64>)
+                -> Light {
+        Light::Spot {
+            position: position,
+            direction: direction.normalize(),
+            color: color,
+            attenuation: attenuation,
+            inner_angle: inner_angle,
+            outer_angle: outer_angle,
+        }
+    }
+
+    /// The normalized direction from a world space `point` towards the light, and the color of
+    /// the light arriving there.
+    pub fn illuminate(&self, point: Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
+        match *self {
+            Light::Directional { direction, color } => (-direction, color),
+            Light::Point { position, color, attenuation } => {
+                let to_light = position - point;
+                let distance = to_light.length();
+                (to_light / distance, color * attenuation.factor(distance))
+            }
+            Light::Spot { position, direction, color, attenuation, inner_angle, outer_angle } => {
+                let to_light = position - point;
+                let distance = to_light.length();
+                let to_light = to_light / distance;
+
+                let cos_angle = (-to_light).dot(direction);
+                let cos_inner = inner_angle.s.cos();
+                let cos_outer = outer_angle.s.cos();
+                let cone = if cos_angle >= cos_inner {
+                    1.0
+                } else if cos_angle <= cos_outer {
+                    0.0
+                } else {
+                    (cos_angle - cos_outer) / (cos_inner - cos_outer)
+                };
+
+                (to_light, color * (attenuation.factor(distance) * cone))
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::cmp;
+use std::mem;
+
+use bitmap::pixel_format::Rgb24;
+use raster::Band;
+use rect::Point2i;
+use rect::Point3f;
+use rect::Rect;
+
+/// How much closer than the surface beneath them lines and points are taken to be, so that
+/// edges drawn over their own faces don't z-fight with them.
+const DEPTH_BIAS: f64 = 1e-5;
+
+/// A single depth tested pixel.
+pub fn draw_point(band: &mut Band, clip: Rect, p: Point3f, color: Rgb24) {
+    let pt = Point2i::new(p.x.floor() as i32, p.y.floor() as i32);
+    blend(band, clip, pt, p.z, color, 1.0);
+}
+
+/// A one pixel wide, depth tested Bresenham line. Lines are tested against the depth buffer
+/// but don't write to it, like the wireframes of most modelling tools.
+pub fn draw_line(band: &mut Band, clip: Rect, a: Point3f, b: Point3f, color: Rgb24) {
+    let (a, b) = match clip_to_rect(a, b, clip) {
+        Some(segment) => segment,
+        None => return,
+    };
+
+    let (x0, y0) = (a.x.floor() as i32, a.y.floor() as i32);
+    let (x1, y1) = (b.x.floor() as i32, b.y.floor() as i32);
+
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let steps = cmp::max(dx, -dy);
+
+    let mut err = dx + dy;
+    let (mut x, mut y) = (x0, y0);
+
+    for i in 0..(steps + 1) {
+        // depth is linear in screen space after the perspective divide
+        let t = if steps == 0 { 0.0 } else { i as f64 / steps as f64 };
+        blend(band, clip, Point2i::new(x, y), a.z + (b.z - a.z) * t, color, 1.0);
+
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Xiaolin Wu's antialiased line: two pixels per step along the major axis, blended by how
+/// close the line passes to each. The ends aren't faded in, which is hard to spot for the
+/// short lines of a wireframe.
+pub fn draw_line_antialiased(band: &mut Band, clip: Rect, a: Point3f, b: Point3f, color: Rgb24) {
+    let (a, b) = match clip_to_rect(a, b, clip) {
+        Some(segment) => segment,
+        None => return,
+    };
+
+    let steep = (b.y - a.y).abs() > (b.x - a.x).abs();
+
+    // in (major, minor) axis order, moved so that pixel centers are on whole numbers
+    let (mut x0, mut y0, mut x1, mut y1) = if steep {
+        (a.y - 0.5, a.x - 0.5, b.y - 0.5, b.x - 0.5)
+    } else {
+        (a.x - 0.5, a.y - 0.5, b.x - 0.5, b.y - 0.5)
+    };
+    let (mut z0, mut z1) = (a.z, b.z);
+
+    if x0 > x1 {
+        mem::swap(&mut x0, &mut x1);
+        mem::swap(&mut y0, &mut y1);
+        mem::swap(&mut z0, &mut z1);
+    }
+
+    let gradient = if x1 - x0 == 0.0 { 0.0 } else { (y1 - y0) / (x1 - x0) };
+    let start = x0.round() as i32;
+    let end = x1.round() as i32;
+
+    for major in start..(end + 1) {
+        let t = if end == start { 0.0 } else { (major - start) as f64 / (end - start) as f64 };
+        let z = z0 + (z1 - z0) * t;
+        let minor = y0 + gradient * (major as f64 - x0);
+        let minor_floor = minor.floor();
+        let frac = minor - minor_floor;
+
+        for &(offset, coverage) in [(0, 1.0 - frac), (1, frac)].iter() {
+            let minor = minor_floor as i32 + offset;
+            let pt = if steep {
+                Point2i::new(minor, major)
+            } else {
+                Point2i::new(major, minor)
+            };
+            blend(band, clip, pt, z, color, coverage);
+        }
+    }
+}
+
+fn blend(band: &mut Band, clip: Rect, pt: Point2i, z: f64, color: Rgb24, coverage: f64) {
+    if pt.x < clip.left || pt.x >= clip.right || pt.y < clip.top || pt.y >= clip.bottom {
+        return;
+    }
+
+    // lines have no width to cover samples with, so all of a pixel's samples get the same
+    let first = band.offset(pt);
+    for offset in first..(first + band.multisample.samples()) {
+        if band.depth[offset] < z - DEPTH_BIAS {
+            continue;
+        }
+
+        band.color[offset] = if coverage >= 1.0 {
+            color
+        } else {
+            let dst = band.color[offset].to_vec();
+            Rgb24::from(dst + (color.to_vec() - dst) * coverage)
+        };
+    }
+}
+
+/// Liang–Barsky clipping to a pixel or so around `rect`, so that lines from far off screen
+/// don't get walked a pixel at a time.
+fn clip_to_rect(a: Point3f, b: Point3f, rect: Rect) -> Option<(Point3f, Point3f)> {
+    let (x_min, x_max) = (rect.left as f64 - 1.0, rect.right as f64 + 1.0);
+    let (y_min, y_max) = (rect.top as f64 - 1.0, rect.bottom as f64 + 1.0);
+    let d = b - a;
+
+    let mut t0 = 0.0;
+    let mut t1 = 1.0;
+
+    let sides = [(-d.x, a.x - x_min), (d.x, x_max - a.x), (-d.y, a.y - y_min), (d.y, y_max - a.y)];
+
+    for &(p, q) in sides.iter() {
+        if p == 0.0 {
+            // parallel to this side, and either inside or not at all
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                if t > t1 {
+                    return None;
+                }
+                if t > t0 {
+                    t0 = t;
+                }
+            } else {
+                if t < t0 {
+                    return None;
+                }
+                if t < t1 {
+                    t1 = t;
+                }
+            }
+        }
+    }
+
+    Some((a + d * t0, a + d * t1))
+}
@@ -1,6 +1,7 @@
 #![feature(alloc_system)]
 extern crate alloc_system;
 
+#[cfg(feature = "window")]
 extern crate sdl2;
 extern crate cgmath;
 extern crate time;
@@ -8,114 +9,257 @@ extern crate time;
 mod bitmap;
 mod device;
 mod camera;
+mod clipping;
+#[cfg(feature = "window")]
+mod controller;
+#[cfg(feature = "window")]
+mod input;
 mod mesh;
+mod light;
+mod line;
+mod material;
 mod math;
 mod obj_importer;
+mod presenter;
+mod raster;
 mod rect;
+mod scene;
+mod shader;
+mod shadow;
+mod transform;
+mod viewport;
 
-use device::{Device, EventPumpAction};
+use device::{Device, ShadingMode};
+use raster::{CullMode, RasterizerState};
 use camera::Camera;
+use shadow::ShadowSettings;
+use bitmap::pixel_format::Rgb24;
+#[cfg(feature = "window")]
+use presenter::Window;
+#[cfg(feature = "window")]
+use controller::{CameraController, OrbitController, FlyController};
+#[cfg(feature = "window")]
+use input::{ActionMap, Binding, InputEvent, InputState, Key};
+#[cfg(feature = "window")]
+use bitmap::scale::Filter;
+#[cfg(feature = "window")]
+use raster::{FillMode, Multisample};
+#[cfg(not(feature = "window"))]
+use presenter::Offscreen;
 
-use sdl2::pixels::Color;
-
-use cgmath::Vector;
 use cgmath::Vector3;
 
-use mesh::Mesh;
-use mesh::Face;
+use light::{Light, Attenuation};
+use material::Material;
+use scene::Scene;
 
 const WIDTH: u32 = 640;
 const HEIGHT: u32 = 480;
 
+/// What the keyboard can do in the window.
+#[cfg(feature = "window")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Action {
+    Quit,
+    OrbitCamera,
+    FlyCamera,
+    LowerRenderScale,
+    RaiseRenderScale,
+    ToggleUpscaleFilter,
+    CycleFillMode,
+    ToggleLineAntialiasing,
+    CycleMultisample,
+    ToggleFxaa,
+    ToggleShadows,
+    ToggleShadowMapOverlay,
+}
+
+/// The tinyrenderer head, with its diffuse texture if it's been put next to the model, lit by
+/// a key light over the camera's shoulder and a dim, warm fill from the side.
+fn build_scene() -> Scene {
+    let mut camera = Camera::new();
+    camera.set_aspect(WIDTH as f64 / HEIGHT as f64);
+
+    let mut scene = Scene::new(camera);
+
+    let mut mesh_head = obj_importer::import_file("./obj/african_head.obj").ok().unwrap();
+    mesh_head.name = "head".to_owned();
+
+    let mut material = Material::new(Vector3::new(1.0, 1.0, 1.0),
+                                     Vector3::new(0.3, 0.3, 0.3),
+                                     32.0)
+                           .with_ambient(Vector3::new(0.1, 0.1, 0.1));
+    if let Ok(texture) = bitmap::tga::import_file("./obj/african_head_diffuse.tga") {
+        material = material.with_diffuse_map(texture);
+    }
+    mesh_head.set_material(scene.add_material("head", material));
+    scene.add_mesh(mesh_head, None);
+
+    scene.add_light(Light::directional(Vector3::new(-0.3, -0.3, -1.0),
+                                       Vector3::new(0.9, 0.9, 0.9)));
+    scene.add_light(Light::point(Vector3::new(3.0, 1.0, 1.0),
+                                 Vector3::new(0.6, 0.45, 0.3),
+                                 Attenuation::new(1.0, 0.0, 0.05)));
+
+    scene
+}
+
+#[cfg(not(feature = "window"))]
+fn main() {
+    let mut device = Device::new(WIDTH, HEIGHT);
+    let mut target = Offscreen::new(WIDTH, HEIGHT);
+    device.set_shading(ShadingMode::BlinnPhong);
+    device.set_rasterizer_state(RasterizerState {
+        cull_mode: CullMode::Back,
+        ..Default::default()
+    });
+    device.set_shadows(Some(ShadowSettings::default()));
+
+    let scene = build_scene();
+
+    device.clear(Rgb24::new(0, 0, 128));
+    device.render(&scene);
+    device.present(&mut target);
+
+    target.save_ppm("./frame.ppm").unwrap();
+}
+
+#[cfg(feature = "window")]
 fn main() {
-    let mut device = Device::new("soft-renderer", WIDTH, HEIGHT);
+    let mut window = Window::new("soft-renderer", WIDTH, HEIGHT);
+    let mut device = Device::new(WIDTH, HEIGHT);
+    device.set_shading(ShadingMode::BlinnPhong);
+    device.set_rasterizer_state(RasterizerState {
+        cull_mode: CullMode::Back,
+        ..Default::default()
+    });
+    device.set_shadows(Some(ShadowSettings::default()));
 
     let mut ticks = 0;
 
-    let cam = Camera::new();
+    let mut scene = build_scene();
 
-    let mut mesh_head = obj_importer::import_file("./obj/african_head.obj").ok().unwrap();
+    let mut actions = ActionMap::new();
+    actions.bind(Action::Quit, Binding::Key(Key::Escape));
+    actions.bind(Action::OrbitCamera, Binding::Key(Key::Num1));
+    actions.bind(Action::FlyCamera, Binding::Key(Key::Num2));
+    actions.bind(Action::LowerRenderScale, Binding::Key(Key::Minus));
+    actions.bind(Action::RaiseRenderScale, Binding::Key(Key::Equals));
+    actions.bind(Action::ToggleUpscaleFilter, Binding::Key(Key::B));
+    actions.bind(Action::CycleFillMode, Binding::Key(Key::Tab));
+    actions.bind(Action::ToggleLineAntialiasing, Binding::Key(Key::L));
+    actions.bind(Action::CycleMultisample, Binding::Key(Key::M));
+    actions.bind(Action::ToggleFxaa, Binding::Key(Key::F));
+    actions.bind(Action::ToggleShadows, Binding::Key(Key::H));
+    actions.bind(Action::ToggleShadowMapOverlay, Binding::Key(Key::V));
 
-    let mut mesh_cube = Mesh::new("cube",
-                                  vec![Vector3::new(-0.1, 0.1, 0.1),
-                                       Vector3::new(0.1, 0.1, 0.1),
-                                       Vector3::new(-0.1, -0.1, 0.1),
-                                       Vector3::new(0.1, -0.1, 0.1),
-                                       Vector3::new(-0.1, 0.1, -0.1),
-                                       Vector3::new(0.1, 0.1, -0.1),
-                                       Vector3::new(0.1, -0.1, -0.1),
-                                       Vector3::new(-0.1, -0.1, -0.1)],
-                                  vec![Face::new(0, 1, 2),
-                                       Face::new(1, 2, 3),
-                                       Face::new(1, 3, 6),
-                                       Face::new(1, 5, 6),
-                                       Face::new(0, 1, 4),
-                                       Face::new(1, 4, 5),
-                                       Face::new(2, 3, 7),
-                                       Face::new(3, 6, 7),
-                                       Face::new(0, 2, 7),
-                                       Face::new(0, 4, 7),
-                                       Face::new(4, 5, 6),
-                                       Face::new(4, 6, 7)]);
-
-    let mut mesh_cube2 = Mesh::new("cube",
-                                   vec![Vector3::new(-0.05, 0.05, 0.05),
-                                        Vector3::new(0.05, 0.05, 0.05),
-                                        Vector3::new(-0.05, -0.05, 0.05),
-                                        Vector3::new(0.05, -0.05, 0.05),
-                                        Vector3::new(-0.05, 0.05, -0.05),
-                                        Vector3::new(0.05, 0.05, -0.05),
-                                        Vector3::new(0.05, -0.05, -0.05),
-                                        Vector3::new(-0.05, -0.05, -0.05)],
-                                   vec![Face::new(0, 1, 2),
-                                        Face::new(1, 2, 3),
-                                        Face::new(1, 3, 6),
-                                        Face::new(1, 5, 6),
-                                        Face::new(0, 1, 4),
-                                        Face::new(1, 4, 5),
-                                        Face::new(2, 3, 7),
-                                        Face::new(3, 6, 7),
-                                        Face::new(0, 2, 7),
-                                        Face::new(0, 4, 7),
-                                        Face::new(4, 5, 6),
-                                        Face::new(4, 6, 7)]);
-
-
-    mesh_cube.set_position(Vector3::zero());
+    let mut input = InputState::new();
+    let mut controller: Box<CameraController> =
+        Box::new(OrbitController::from_camera(scene.camera()));
+    let mut last_frame = time::precise_time_s();
 
     'running: loop {
         let time_start = time::now();
 
-        match device.poll_events() {
-            EventPumpAction::Quit => break 'running,
-            EventPumpAction::Continue => {}
+        let events = window.poll_events(&mut input);
+        if input.quit_requested() || actions.was_triggered(Action::Quit, &input) {
+            break 'running;
+        }
+
+        if actions.was_triggered(Action::OrbitCamera, &input) {
+            controller = Box::new(OrbitController::from_camera(scene.camera()));
+        } else if actions.was_triggered(Action::FlyCamera, &input) {
+            controller = Box::new(FlyController::from_camera(scene.camera()));
+        }
+
+        if actions.was_triggered(Action::LowerRenderScale, &input) {
+            let scale = (device.render_scale() - 0.25).max(0.25);
+            device.set_render_scale(scale);
+        } else if actions.was_triggered(Action::RaiseRenderScale, &input) {
+            let scale = (device.render_scale() + 0.25).min(1.0);
+            device.set_render_scale(scale);
+        }
+        if actions.was_triggered(Action::ToggleUpscaleFilter, &input) {
+            device.set_upscale_filter(match device.upscale_filter() {
+                Filter::Nearest => Filter::Bilinear,
+                Filter::Bilinear => Filter::Nearest,
+            });
+        }
+
+        if actions.was_triggered(Action::CycleFillMode, &input) {
+            let mut rasterizer = device.rasterizer_state();
+            rasterizer.fill_mode = match rasterizer.fill_mode {
+                FillMode::Solid => FillMode::Wireframe,
+                FillMode::Wireframe => FillMode::SolidWireframe,
+                FillMode::SolidWireframe => FillMode::Points,
+                FillMode::Points => FillMode::Solid,
+            };
+            device.set_rasterizer_state(rasterizer);
         }
+        if actions.was_triggered(Action::ToggleLineAntialiasing, &input) {
+            let mut rasterizer = device.rasterizer_state();
+            rasterizer.antialiased_lines = !rasterizer.antialiased_lines;
+            device.set_rasterizer_state(rasterizer);
+        }
+        if actions.was_triggered(Action::CycleMultisample, &input) {
+            device.set_multisample(match device.multisample() {
+                Multisample::Off => Multisample::X2,
+                Multisample::X2 => Multisample::X4,
+                Multisample::X4 => Multisample::X8,
+                Multisample::X8 => Multisample::Off,
+            });
+        }
+        if actions.was_triggered(Action::ToggleFxaa, &input) {
+            let fxaa = device.fxaa();
+            device.set_fxaa(!fxaa);
+        }
+        if actions.was_triggered(Action::ToggleShadows, &input) {
+            let shadows = match device.shadows() {
+                Some(_) => None,
+                None => Some(ShadowSettings::default()),
+            };
+            device.set_shadows(shadows);
+        }
+        if actions.was_triggered(Action::ToggleShadowMapOverlay, &input) {
+            // the key light is the first one added
+            let overlay = match device.shadow_map_overlay() {
+                Some(_) => None,
+                None => Some(0),
+            };
+            device.set_shadow_map_overlay(overlay);
+        }
+
+        for event in events.iter() {
+            // minimizing can report a zero sized window, which there's no point drawing
+            if let InputEvent::Resized { width, height } = *event {
+                if width == 0 || height == 0 {
+                    continue;
+                }
+                device.resize(width, height);
+                scene.camera_mut().set_aspect(width as f64 / height as f64);
+            }
+            controller.handle_event(event);
+        }
+
+        let now = time::precise_time_s();
+        controller.update(scene.camera_mut(), now - last_frame);
+        last_frame = now;
 
-        // let old_rotation = mesh_cube.rotation;
-        // mesh_cube.set_rotation(Vector3::new(old_rotation.x + 0.01,
-        //                                     old_rotation.y + 0.01,
-        //                                     old_rotation.z));
-        //
-        // mesh_cube2.set_rotation(Vector3::new(old_rotation.x + 0.01,
-        //                                      old_rotation.y + 0.01,
-        //                                      old_rotation.z));
-        // mesh_head.set_rotation(Vector3::new(old_rotation.x + 0.01,
-        //                                     old_rotation.y + 0.01,
-        //                                     old_rotation.z));
-
-        device.clear(Color::RGB(0, 0, 128));
-        device.render(&cam, vec![&mesh_head]);
+        device.clear(Rgb24::new(0, 0, 128));
+        device.render(&scene);
         // device.test_draw_triangles();
-    //    device.render(&cam, vec![&mesh_cube, &mesh_cube2]);
-        device.present();
+        device.present(&mut window);
 
         let time_end = time::now();
         let elapsed = (time_end - time_start).num_milliseconds();
 
-        println!("frame = {:?}, elapsed = {}ms, fps = {}",
+        let stats = device.stats();
+        println!("frame = {:?}, elapsed = {}ms, fps = {}, triangles = {}, culled = {}",
                  ticks,
                  elapsed,
-                 1000.0 / elapsed as f32);
+                 1000.0 / elapsed as f32,
+                 stats.triangles_drawn,
+                 stats.triangles_culled);
         ticks += 1;
     }
 }
@@ -0,0 +1,43 @@
+use cgmath::Vector3;
+
+use bitmap::Bitmap;
+use bitmap::pixel_format::Rgb24;
+
+/// Surface properties for the lighting models. Colors are linear RGB in [0, 1].
+#[derive(Debug)]
+pub struct Material {
+    pub ambient: Vector3<f64>,
+    pub diffuse: Vector3<f64>,
+    pub specular: Vector3<f64>,
+    /// Specular exponent; higher is shinier.
+    pub shininess: f64,
+    /// Multiplied into `diffuse` where the mesh has texture coordinates.
+    pub diffuse_map: Option<Bitmap<Rgb24>>,
+}
+
+impl Material {
+    pub fn new(diffuse: Vector3<f64>, specular: Vector3<f64>, shininess: f64) -> Material {
+        Material {
+            ambient: Vector3::new(0.0, 0.0, 0.0),
+            diffuse: diffuse,
+            specular: specular,
+            shininess: shininess,
+            diffuse_map: None,
+        }
+    }
+
+    pub fn with_ambient(self, ambient: Vector3<f64>) -> Material {
+        Material { ambient: ambient, ..self }
+    }
+
+    pub fn with_diffuse_map(self, diffuse_map: Bitmap<Rgb24>) -> Material {
+        Material { diffuse_map: Some(diffuse_map), ..self }
+    }
+}
+
+impl Default for Material {
+    /// Plain white and completely matte.
+    fn default() -> Material {
+        Material::new(Vector3::new(1.0, 1.0, 1.0), Vector3::new(0.0, 0.0, 0.0), 1.0)
+    }
+}
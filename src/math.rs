@@ -1,3 +1,5 @@
+use cgmath::Vector3;
+
 pub trait Clamp {
 	fn clamp(self, low: Self, high: Self) -> Self;
 }
@@ -18,3 +20,8 @@ impl Interpolate for f64 {
         min + (max - min) * val.clamp(0.0, 1.0)
     }
 }
+
+/// Component-wise product, for modulating one color by another.
+pub fn modulate(a: Vector3<f64>, b: Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(a.x * b.x, a.y * b.y, a.z * b.z)
+}
@@ -1,37 +1,101 @@
 extern crate cgmath;
 
 use cgmath::Vector;
+use cgmath::EuclideanVector;
+use cgmath::Vector2;
 use cgmath::Vector3;
 
+use std::rc::Rc;
 use std::vec::Vec;
 
+use material::Material;
+
 // TODO(tang): should these fields have accessors?
 #[derive(Debug)]
 pub struct Mesh {
     pub name: String,
     pub vertices: Vec<Vector3<f64>>,
     pub faces: Vec<Face>,
-    pub position: Vector3<f64>,
-    pub rotation: Vector3<f64>, // TODO: consider using quaternions here
+    pub texcoords: Vec<Vector2<f64>>,
+    pub normals: Vec<Vector3<f64>>,
+    pub material: Rc<Material>,
 }
 
 impl Mesh {
+    /// Plain geometry, with every face lit by its own normal; call `compute_smooth_normals`
+    /// for shading that blends across edges.
     pub fn new(name: &str, verts: Vec<Vector3<f64>>, faces: Vec<Face>) -> Mesh {
+        Mesh::from_parts(name, verts, Vec::new(), Vec::new(), faces)
+    }
+
+    /// A mesh with texture coordinates and normals of its own, which `faces` index into.
+    /// Nothing is computed; faces without normals are lit with their face normal.
+    pub fn from_parts(name: &str,
+                      verts: Vec<Vector3<f64>>,
+                      texcoords: Vec<Vector2<f64>>,
+                      normals: Vec<Vector3<f64>>,
+                      faces: Vec<Face>)
+                      -> Mesh {
         Mesh {
             name: name.to_owned(),
             vertices: verts,
             faces: faces,
-            position: Vector3::zero(),
-            rotation: Vector3::zero(),
+            texcoords: texcoords,
+            normals: normals,
+            material: Rc::new(Material::default()),
+        }
+    }
+
+    pub fn set_material(&mut self, material: Rc<Material>) {
+        self.material = material;
+    }
+
+    /// Texture coordinates of the corners of a face, or the origin if the face doesn't have any.
+    pub fn face_texcoords(&self, face: &Face) -> [Vector2<f64>; 3] {
+        match face.texcoords {
+            Some(t) => [self.texcoords[t[0]], self.texcoords[t[1]], self.texcoords[t[2]]],
+            None => [Vector2::zero(); 3],
+        }
+    }
+
+    /// Normals of the corners of a face, falling back to the face's own normal.
+    pub fn face_normals(&self, face: &Face) -> [Vector3<f64>; 3] {
+        match face.normals {
+            Some(n) => [self.normals[n[0]], self.normals[n[1]], self.normals[n[2]]],
+            None => [self.face_normal(face); 3],
         }
     }
 
-    pub fn set_position(&mut self, new_pos: Vector3<f64>) {
-        self.position = new_pos;
+    /// The outward normal of a counter-clockwise face.
+    pub fn face_normal(&self, face: &Face) -> Vector3<f64> {
+        self.face_cross(face).normalize()
     }
 
-    pub fn set_rotation(&mut self, new_rot: Vector3<f64>) {
-        self.rotation = new_rot;
+    fn face_cross(&self, face: &Face) -> Vector3<f64> {
+        (self.vertices[face.b] - self.vertices[face.a])
+            .cross(self.vertices[face.c] - self.vertices[face.a])
+    }
+
+    /// Replaces the normals with one per vertex, averaged over the faces that share it and
+    /// weighted by their area.
+    pub fn compute_smooth_normals(&mut self) {
+        let mut normals = vec![Vector3::zero(); self.vertices.len()];
+
+        for face in self.faces.iter() {
+            // left unnormalized so that bigger faces count for more
+            let cross = self.face_cross(face);
+            for &i in [face.a, face.b, face.c].iter() {
+                normals[i] = normals[i] + cross;
+            }
+        }
+
+        self.normals = normals.into_iter()
+                              .map(|n| if n.length2() > 0.0 { n.normalize() } else { n })
+                              .collect();
+
+        for face in self.faces.iter_mut() {
+            face.normals = Some([face.a, face.b, face.c]);
+        }
     }
 }
 
@@ -40,12 +104,28 @@ pub struct Face {
     pub a: usize,
     pub b: usize,
     pub c: usize,
-    // TODO: add texture coordinates
-    // TODO: add normals
+    /// Indices into `Mesh::texcoords` for each of a, b and c.
+    pub texcoords: Option<[usize; 3]>,
+    /// Indices into `Mesh::normals` for each of a, b and c.
+    pub normals: Option<[usize; 3]>,
 }
 
 impl Face {
     pub fn new(a: usize, b: usize, c: usize) -> Face {
-        Face { a: a, b: b, c: c }
+        Face {
+            a: a,
+            b: b,
+            c: c,
+            texcoords: None,
+            normals: None,
+        }
+    }
+
+    pub fn with_texcoords(self, ta: usize, tb: usize, tc: usize) -> Face {
+        Face { texcoords: Some([ta, tb, tc]), ..self }
+    }
+
+    pub fn with_normals(self, na: usize, nb: usize, nc: usize) -> Face {
+        Face { normals: Some([na, nb, nc]), ..self }
     }
 }
@@ -5,6 +5,7 @@ use std::io::Result;
 
 use std::f64;
 
+use cgmath::Vector2;
 use cgmath::Vector3;
 
 use mesh::Mesh;
@@ -20,27 +21,79 @@ pub fn import_file(filename: &str) -> Result<Mesh> {
 
 fn read_obj<I>(mesh_name: &str, file: I) -> Mesh where I: Iterator<Item = Result<String>> {
     let mut verts = Vec::new();
+    let mut texcoords = Vec::new();
+    let mut normals = Vec::new();
     let mut faces = Vec::new();
 
     for o_line in file {
         let line = o_line.unwrap();
+        let counts = (verts.len(), texcoords.len(), normals.len());
 
-        match parse_line(line) {
+        match parse_line(line, counts) {
             Some(ObjLine::V(vertex)) => verts.push(vertex),
+            Some(ObjLine::Vt(texcoord)) => texcoords.push(texcoord),
+            Some(ObjLine::Vn(normal)) => normals.push(normal),
             Some(ObjLine::F(face)) => faces.push(face),
             _ => {},
         }
     }
 
-    Mesh::new(mesh_name, verts, faces)
+    let mut mesh = Mesh::from_parts(mesh_name, verts, texcoords, normals, faces);
+
+    if mesh.normals.is_empty() {
+        mesh.compute_smooth_normals();
+    }
+
+    mesh
 }
 
 enum ObjLine {
     V(Vector3<f64>),
+    Vt(Vector2<f64>),
+    Vn(Vector3<f64>),
     F(Face),
 }
 
-fn parse_line(line: String) -> Option<ObjLine> {
+/// One corner of an `f` line, `v`, `v/vt`, `v//vn` or `v/vt/vn`, converted to 0-indexing.
+struct FaceCorner {
+    vertex: usize,
+    texcoord: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// How many vertices, texture coordinates and normals have been read so far.
+type Counts = (usize, usize, usize);
+
+/// OBJ indices start at 1, and negative ones count back from the last element read so far,
+/// -1 being the latest. Either way they can only refer to elements that have already been
+/// read.
+fn parse_index(index: &str, count: usize) -> Option<usize> {
+    match index.parse::<isize>() {
+        Ok(i) if i > 0 && i as usize <= count => Some(i as usize - 1),
+        Ok(i) if i < 0 && (-i) as usize <= count => Some(count - (-i) as usize),
+        _ => None,
+    }
+}
+
+fn parse_face_corner(s: &str, counts: Counts) -> Option<FaceCorner> {
+    let (vertex_count, texcoord_count, normal_count) = counts;
+    let mut indices = s.split('/');
+
+    let vertex = match indices.next().and_then(|v| parse_index(v, vertex_count)) {
+        Some(v) => v,
+        None => return None,
+    };
+    let texcoord = indices.next().and_then(|vt| parse_index(vt, texcoord_count));
+    let normal = indices.next().and_then(|vn| parse_index(vn, normal_count));
+
+    Some(FaceCorner {
+        vertex: vertex,
+        texcoord: texcoord,
+        normal: normal,
+    })
+}
+
+fn parse_line(line: String, counts: Counts) -> Option<ObjLine> {
     let mut iter = line.split_whitespace();
 
     let line_type = iter.next();
@@ -57,19 +110,41 @@ fn parse_line(line: String) -> Option<ObjLine> {
                 None
             }
         },
+        Some("vn") => {
+            let values : Vec<f64> = iter.filter_map(|s| s.parse().ok()).collect();
+
+            if values.len() == 3 {
+                Some(ObjLine::Vn(Vector3::new(values[0], values[1], values[2])))
+            } else {
+                None
+            }
+        },
+        Some("vt") => {
+            let values : Vec<f64> = iter.filter_map(|s| s.parse().ok()).collect();
+
+            // the optional third (w) component is ignored
+            if values.len() >= 2 {
+                Some(ObjLine::Vt(Vector2::new(values[0], values[1])))
+            } else {
+                None
+            }
+        },
         Some("f") => {
-            let indices : Vec<usize> = iter.filter_map(|s| {
-                // is there a better way to do this?
-                let mut indices = s.split('/');
-                match indices.next() {
-                    Some(s) => s.parse().ok(),
-                    None => None,
-                }
-            }).collect();
+            let corners : Vec<FaceCorner> = iter.filter_map(|s| parse_face_corner(s, counts))
+                                                  .collect();
 
-            // TODO: 1-index, add texture coordinates, normals
-            if indices.len() == 3 {
-                let face = Face::new(indices[0] - 1, indices[1] - 1, indices[2] - 1);
+            if corners.len() == 3 {
+                let mut face = Face::new(corners[0].vertex, corners[1].vertex, corners[2].vertex);
+                if let (Some(ta), Some(tb), Some(tc)) = (corners[0].texcoord,
+                                                         corners[1].texcoord,
+                                                         corners[2].texcoord) {
+                    face = face.with_texcoords(ta, tb, tc);
+                }
+                if let (Some(na), Some(nb), Some(nc)) = (corners[0].normal,
+                                                         corners[1].normal,
+                                                         corners[2].normal) {
+                    face = face.with_normals(na, nb, nc);
+                }
                 // println!("f {:?}", face);
                 Some(ObjLine::F(face))
             } else {
@@ -79,3 +154,30 @@ fn parse_line(line: String) -> Option<ObjLine> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use obj_importer::parse_index;
+
+    #[test]
+    fn positive_indices_count_from_one() {
+        assert_eq!(parse_index("1", 10), Some(0));
+        assert_eq!(parse_index("10", 10), Some(9));
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_read() {
+        assert_eq!(parse_index("-1", 10), Some(9));
+        assert_eq!(parse_index("-10", 10), Some(0));
+    }
+
+    #[test]
+    fn invalid_indices_are_rejected() {
+        assert_eq!(parse_index("0", 10), None);
+        assert_eq!(parse_index("11", 10), None);
+        assert_eq!(parse_index("-11", 10), None);
+        assert_eq!(parse_index("1", 0), None);
+        assert_eq!(parse_index("", 10), None);
+        assert_eq!(parse_index("x", 10), None);
+    }
+}
@@ -0,0 +1,15 @@
+pub mod offscreen;
+#[cfg(feature = "window")]
+pub mod window;
+
+use bitmap::Bitmap;
+use bitmap::pixel_format::TransferToRgb;
+
+pub use presenter::offscreen::Offscreen;
+#[cfg(feature = "window")]
+pub use presenter::window::Window;
+
+/// Something that can show (or keep) a finished frame.
+pub trait Presenter {
+    fn present<T>(&mut self, frame: &Bitmap<T>) where T: Copy + Default + TransferToRgb;
+}
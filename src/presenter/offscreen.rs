@@ -0,0 +1,60 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Result;
+use std::io::Write;
+
+use cgmath::Vector2;
+
+use bitmap::Bitmap;
+use bitmap::pixel_format::Rgb24;
+use bitmap::pixel_format::TransferToRgb;
+use presenter::Presenter;
+
+/// Presents into a plain `Bitmap<Rgb24>`, for rendering without a display.
+pub struct Offscreen {
+    frame: Bitmap<Rgb24>,
+}
+
+impl Offscreen {
+    pub fn new(width: u32, height: u32) -> Offscreen {
+        Offscreen { frame: Bitmap::new(width, height) }
+    }
+
+    /// The last presented frame.
+    pub fn frame(&self) -> &Bitmap<Rgb24> {
+        &self.frame
+    }
+
+    /// Writes the last presented frame as a binary PPM.
+    pub fn save_ppm(&self, filename: &str) -> Result<()> {
+        let f = try!(File::create(filename));
+        let mut file = BufWriter::new(f);
+
+        try!(write!(file, "P6\n{} {}\n255\n", self.frame.width(), self.frame.height()));
+        for y in 0..self.frame.height() {
+            for x in 0..self.frame.width() {
+                let pixel = self.frame.get_pixel(Vector2::new(x, y));
+                try!(file.write_all(&[pixel.r, pixel.g, pixel.b]));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Presenter for Offscreen {
+    fn present<T>(&mut self, frame: &Bitmap<T>)
+        where T: Copy + Default + TransferToRgb
+    {
+        if frame.width() != self.frame.width() || frame.height() != self.frame.height() {
+            self.frame = Bitmap::new(frame.width(), frame.height());
+        }
+
+        for y in 0..frame.height() {
+            for x in 0..frame.width() {
+                let point = Vector2::new(x, y);
+                let (r, g, b) = frame.get_pixel(point).transfer();
+                self.frame.set_pixel(point, Rgb24::new(r, g, b));
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+extern crate sdl2;
+
+use bitmap::Bitmap;
+use bitmap::pixel_format::TransferToRgb;
+use input::{InputEvent, InputState, Key, MouseButton};
+use presenter::Presenter;
+
+use sdl2::event::{Event, WindowEventId};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::Renderer;
+use sdl2::render::Texture;
+use sdl2::EventPump;
+
+/// Presents frames to an SDL window through a streaming texture.
+pub struct Window<'a> {
+    renderer: Renderer<'a>,
+    texture: Texture,
+    /// The texture is recreated whenever a frame of a different size comes along.
+    texture_size: (u32, u32),
+    event_pump: EventPump,
+}
+
+impl<'a> Window<'a> {
+    pub fn new(title: &str, width: u32, height: u32) -> Window<'a> {
+        let sdl_context = sdl2::init().unwrap();
+        let video_subsystem = sdl_context.video().unwrap();
+
+        let window = video_subsystem.window(title, width, height)
+                                    .position_centered()
+                                    .resizable()
+                                    .build()
+                                    .unwrap();
+
+        let renderer = window.renderer().build().unwrap();
+
+        let texture = renderer.create_texture_streaming(PixelFormatEnum::RGB24, width, height)
+                              .unwrap();
+
+        let event_pump = sdl_context.event_pump().unwrap();
+
+        Window {
+            renderer: renderer,
+            texture: texture,
+            texture_size: (width, height),
+            event_pump: event_pump,
+        }
+    }
+
+    /// The size of the window's drawable area, in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        self.renderer.window().unwrap().size()
+    }
+
+    /// Drains SDL's event queue, starting a new frame of `input` and feeding it everything that
+    /// happened. The events are returned too, in the order they arrived, for anything that
+    /// cares about more than the end result.
+    pub fn poll_events(&mut self, input: &mut InputState) -> Vec<InputEvent> {
+        input.begin_frame();
+
+        let events: Vec<InputEvent> = self.event_pump
+                                          .poll_iter()
+                                          .filter_map(|event| translate_event(&event))
+                                          .collect();
+        for event in events.iter() {
+            input.handle_event(event);
+        }
+
+        events
+    }
+
+    fn copy_bitmap_to_texture<T>(src_bitmap: &Bitmap<T>, dest_texture: &mut Texture)
+        where T: Copy + Default + TransferToRgb
+    {
+        let slice = src_bitmap.slice();
+        let width = src_bitmap.width();
+        let height = src_bitmap.height();
+
+        // rows of the texture can be padded, so they're `pitch` bytes apart rather than
+        // `width * 3`
+        dest_texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                        for y in 0..height {
+                            for x in 0..width {
+                                let offset = (y * width + x) as usize;
+                                let woffset = y as usize * pitch + x as usize * 3;
+                                let (r, g, b) = slice[offset].transfer();
+
+                                buffer[woffset] = r;
+                                buffer[woffset + 1] = g;
+                                buffer[woffset + 2] = b;
+                            }
+                        }
+                    })
+                    .unwrap();
+    }
+}
+
+impl<'a> Presenter for Window<'a> {
+    fn present<T>(&mut self, frame: &Bitmap<T>)
+        where T: Copy + Default + TransferToRgb
+    {
+        if (frame.width(), frame.height()) != self.texture_size {
+            self.texture = self.renderer
+                               .create_texture_streaming(PixelFormatEnum::RGB24,
+                                                         frame.width(),
+                                                         frame.height())
+                               .unwrap();
+            self.texture_size = (frame.width(), frame.height());
+        }
+
+        Window::copy_bitmap_to_texture(frame, &mut self.texture);
+        self.renderer.copy(&self.texture, None, None);
+        self.renderer.present();
+    }
+}
+
+fn translate_event(event: &Event) -> Option<InputEvent> {
+    match *event {
+        Event::Quit { .. } => Some(InputEvent::Quit),
+        Event::KeyDown { keycode: Some(keycode), repeat, .. } => {
+            translate_key(keycode).map(|key| InputEvent::KeyDown { key: key, repeat: repeat })
+        }
+        Event::KeyUp { keycode: Some(keycode), .. } => {
+            translate_key(keycode).map(|key| InputEvent::KeyUp { key: key })
+        }
+        Event::MouseMotion { x, y, xrel, yrel, .. } => {
+            Some(InputEvent::MouseMotion { x: x, y: y, dx: xrel, dy: yrel })
+        }
+        Event::MouseButtonDown { mouse_btn, x, y, .. } => {
+            translate_button(mouse_btn).map(|button| {
+                InputEvent::MouseButtonDown { button: button, x: x, y: y }
+            })
+        }
+        Event::MouseButtonUp { mouse_btn, x, y, .. } => {
+            translate_button(mouse_btn).map(|button| {
+                InputEvent::MouseButtonUp { button: button, x: x, y: y }
+            })
+        }
+        Event::MouseWheel { x, y, .. } => Some(InputEvent::MouseWheel { x: x, y: y }),
+        Event::Window { win_event_id: WindowEventId::Resized, data1, data2, .. } => {
+            Some(InputEvent::Resized { width: data1 as u32, height: data2 as u32 })
+        }
+        Event::Window { win_event_id: WindowEventId::FocusGained, .. } => {
+            Some(InputEvent::FocusGained)
+        }
+        Event::Window { win_event_id: WindowEventId::FocusLost, .. } => {
+            Some(InputEvent::FocusLost)
+        }
+        _ => None,
+    }
+}
+
+fn translate_key(keycode: Keycode) -> Option<Key> {
+    let key = match keycode {
+        Keycode::A => Key::A,
+        Keycode::B => Key::B,
+        Keycode::C => Key::C,
+        Keycode::D => Key::D,
+        Keycode::E => Key::E,
+        Keycode::F => Key::F,
+        Keycode::G => Key::G,
+        Keycode::H => Key::H,
+        Keycode::I => Key::I,
+        Keycode::J => Key::J,
+        Keycode::K => Key::K,
+        Keycode::L => Key::L,
+        Keycode::M => Key::M,
+        Keycode::N => Key::N,
+        Keycode::O => Key::O,
+        Keycode::P => Key::P,
+        Keycode::Q => Key::Q,
+        Keycode::R => Key::R,
+        Keycode::S => Key::S,
+        Keycode::T => Key::T,
+        Keycode::U => Key::U,
+        Keycode::V => Key::V,
+        Keycode::W => Key::W,
+        Keycode::X => Key::X,
+        Keycode::Y => Key::Y,
+        Keycode::Z => Key::Z,
+        Keycode::Num0 => Key::Num0,
+        Keycode::Num1 => Key::Num1,
+        Keycode::Num2 => Key::Num2,
+        Keycode::Num3 => Key::Num3,
+        Keycode::Num4 => Key::Num4,
+        Keycode::Num5 => Key::Num5,
+        Keycode::Num6 => Key::Num6,
+        Keycode::Num7 => Key::Num7,
+        Keycode::Num8 => Key::Num8,
+        Keycode::Num9 => Key::Num9,
+        Keycode::F1 => Key::F1,
+        Keycode::F2 => Key::F2,
+        Keycode::F3 => Key::F3,
+        Keycode::F4 => Key::F4,
+        Keycode::F5 => Key::F5,
+        Keycode::F6 => Key::F6,
+        Keycode::F7 => Key::F7,
+        Keycode::F8 => Key::F8,
+        Keycode::F9 => Key::F9,
+        Keycode::F10 => Key::F10,
+        Keycode::F11 => Key::F11,
+        Keycode::F12 => Key::F12,
+        Keycode::Escape => Key::Escape,
+        Keycode::Space => Key::Space,
+        Keycode::Return => Key::Return,
+        Keycode::Tab => Key::Tab,
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Delete => Key::Delete,
+        Keycode::Up => Key::Up,
+        Keycode::Down => Key::Down,
+        Keycode::Left => Key::Left,
+        Keycode::Right => Key::Right,
+        Keycode::LShift => Key::LShift,
+        Keycode::RShift => Key::RShift,
+        Keycode::LCtrl => Key::LCtrl,
+        Keycode::RCtrl => Key::RCtrl,
+        Keycode::LAlt => Key::LAlt,
+        Keycode::RAlt => Key::RAlt,
+        Keycode::Minus => Key::Minus,
+        Keycode::Equals => Key::Equals,
+        Keycode::Comma => Key::Comma,
+        Keycode::Period => Key::Period,
+        Keycode::Slash => Key::Slash,
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn translate_button(button: mouse::MouseButton) -> Option<MouseButton> {
+    match button {
+        mouse::MouseButton::Left => Some(MouseButton::Left),
+        mouse::MouseButton::Middle => Some(MouseButton::Middle),
+        mouse::MouseButton::Right => Some(MouseButton::Right),
+        mouse::MouseButton::X1 => Some(MouseButton::X1),
+        mouse::MouseButton::X2 => Some(MouseButton::X2),
+        mouse::MouseButton::Unknown => None,
+    }
+}
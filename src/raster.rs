@@ -0,0 +1,623 @@
+use std::cmp;
+use std::f64;
+
+use bitmap::pixel_format::Rgb24;
+use device::Interpolation;
+use rect::Point2i;
+use rect::Point3f;
+use rect::Rect;
+use shader::{Shader, Uniforms, Fragment, Varying};
+
+/// Width and height of the squares the frame is binned into.
+pub const TILE_SIZE: i32 = 32;
+
+/// Which triangles to throw away based on which way they face.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+/// The winding, as seen on screen, that makes a triangle front facing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrontFace {
+    Clockwise,
+    /// What OBJ files and OpenGL expect.
+    CounterClockwise,
+}
+
+/// What to draw of each triangle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FillMode {
+    Solid,
+    /// Just the edges, in `RasterizerState::line_color`.
+    Wireframe,
+    /// Just the vertices, in `RasterizerState::line_color`.
+    Points,
+    /// Filled, with the edges drawn over the top.
+    SolidWireframe,
+}
+
+impl FillMode {
+    pub fn fills(&self) -> bool {
+        *self == FillMode::Solid || *self == FillMode::SolidWireframe
+    }
+
+    pub fn outlines(&self) -> bool {
+        *self == FillMode::Wireframe || *self == FillMode::SolidWireframe
+    }
+}
+
+/// How many places in each pixel coverage and depth are tested at. Fragments are still only
+/// shaded once per pixel, and the samples are averaged together when the frame is presented.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Multisample {
+    Off,
+    X2,
+    X4,
+    X8,
+}
+
+/// The most samples a pixel can have.
+const MAX_SAMPLES: usize = 8;
+
+impl Multisample {
+    pub fn samples(&self) -> usize {
+        match *self {
+            Multisample::Off => 1,
+            Multisample::X2 => 2,
+            Multisample::X4 => 4,
+            Multisample::X8 => 8,
+        }
+    }
+
+    /// Where the samples are, relative to the pixel center, in sixteenths of a pixel. These are
+    /// the standard Direct3D patterns, which give near-horizontal and near-vertical edges as
+    /// many distinct steps as there are samples.
+    fn pattern(&self) -> &'static [(i64, i64)] {
+        match *self {
+            Multisample::Off => &[(0, 0)],
+            Multisample::X2 => &[(4, 4), (-4, -4)],
+            Multisample::X4 => &[(-2, -6), (6, -2), (-6, 2), (2, 6)],
+            Multisample::X8 => {
+                &[(1, -3), (-1, 3), (5, 1), (-3, -5), (-5, 5), (-7, -1), (3, 7), (7, -7)]
+            }
+        }
+    }
+}
+
+/// Fixed-function settings for turning triangles into pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RasterizerState {
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    pub fill_mode: FillMode,
+    pub line_color: Rgb24,
+    /// Draw lines with Wu's algorithm rather than Bresenham's.
+    pub antialiased_lines: bool,
+}
+
+impl RasterizerState {
+    /// Whether a triangle with the given winding on screen gets thrown away.
+    pub fn culls(&self, clockwise: bool) -> bool {
+        let front = match self.front_face {
+            FrontFace::Clockwise => clockwise,
+            FrontFace::CounterClockwise => !clockwise,
+        };
+
+        match self.cull_mode {
+            CullMode::None => false,
+            CullMode::Back => !front,
+            CullMode::Front => front,
+        }
+    }
+}
+
+impl Default for RasterizerState {
+    fn default() -> RasterizerState {
+        RasterizerState {
+            cull_mode: CullMode::None,
+            front_face: FrontFace::CounterClockwise,
+            fill_mode: FillMode::Solid,
+            line_color: Rgb24::new(255, 255, 255),
+            antialiased_lines: false,
+        }
+    }
+}
+
+/// A vertex after the perspective divide and viewport transform.
+#[derive(Clone, Copy)]
+pub struct ScreenVertex<V> {
+    pub position: Point3f,
+    pub inv_w: f64,
+    pub varying: V,
+}
+
+/// Bits of sub-pixel precision vertex positions are snapped to.
+pub const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_ONE: i64 = 1 << SUBPIXEL_BITS;
+const SUBPIXEL_HALF: i64 = SUBPIXEL_ONE / 2;
+
+/// Vertices further than this many pixels outside the frame would overflow the edge functions.
+/// The clipper's guard band keeps them well inside it, so triangles reaching out that far are
+/// only dropped as a last resort, on viewports too big for the guard band.
+const GUARD_BAND: f64 = (1 << 20) as f64;
+
+/// A triangle that's been through the geometry stages and is ready to fill. Its vertices are
+/// wound so that all three edge functions are positive inside.
+pub struct Triangle<V> {
+    pub vertices: [ScreenVertex<V>; 3],
+    /// The pixels it can touch, already clipped to the render target and viewport.
+    pub bounds: Rect,
+    /// How the vertices were wound on screen before they were put in order.
+    pub clockwise: bool,
+    /// `edges[i]` is the edge opposite `vertices[i]`, so it's zero there and `area` at the
+    /// vertex.
+    edges: [Edge; 3],
+    /// Twice the area, in squared sub-pixel units.
+    area: i64,
+}
+
+impl<V> Triangle<V>
+    where V: Copy
+{
+    /// Snaps the vertices to the sub-pixel grid and sets up the edge functions. Returns `None`
+    /// if the triangle covers no area, or none of `target`.
+    pub fn new(a: ScreenVertex<V>,
+               b: ScreenVertex<V>,
+               c: ScreenVertex<V>,
+               target: Rect)
+               -> Option<Triangle<V>> {
+        for vertex in [a, b, c].iter() {
+            if !(vertex.position.x.abs() < GUARD_BAND && vertex.position.y.abs() < GUARD_BAND) {
+                return None;
+            }
+        }
+
+        let (a, mut b, mut c) = (a, b, c);
+        let fa = to_fixed(a.position);
+        let mut fb = to_fixed(b.position);
+        let mut fc = to_fixed(c.position);
+
+        let mut area = orient(fa, fb, fc);
+        if area == 0 {
+            // all three on a line, or on top of each other
+            return None;
+        }
+
+        let clockwise = area > 0;
+        if !clockwise {
+            // keep the first vertex first; it's the provoking one for flat varyings
+            ::std::mem::swap(&mut b, &mut c);
+            ::std::mem::swap(&mut fb, &mut fc);
+            area = -area;
+        }
+
+        // every pixel whose center could be inside, exclusive on the right and bottom
+        let left = cmp::min(fa.0, cmp::min(fb.0, fc.0)) >> SUBPIXEL_BITS;
+        let top = cmp::min(fa.1, cmp::min(fb.1, fc.1)) >> SUBPIXEL_BITS;
+        let right = (cmp::max(fa.0, cmp::max(fb.0, fc.0)) >> SUBPIXEL_BITS) + 1;
+        let bottom = (cmp::max(fa.1, cmp::max(fb.1, fc.1)) >> SUBPIXEL_BITS) + 1;
+        let bounds = Rect::new(Point2i::new(left as i32, top as i32),
+                               Point2i::new(right as i32, bottom as i32));
+
+        let bounds = match bounds.intersect(target) {
+            Some(rect) if rect.left < rect.right && rect.top < rect.bottom => rect,
+            _ => return None,
+        };
+
+        Some(Triangle {
+            vertices: [a, b, c],
+            bounds: bounds,
+            clockwise: clockwise,
+            edges: [Edge::new(fb, fc), Edge::new(fc, fa), Edge::new(fa, fb)],
+            area: area,
+        })
+    }
+}
+
+type FixedPoint = (i64, i64);
+
+fn to_fixed(position: Point3f) -> FixedPoint {
+    ((position.x * SUBPIXEL_ONE as f64).round() as i64,
+     (position.y * SUBPIXEL_ONE as f64).round() as i64)
+}
+
+/// Twice the signed area of `abc`; positive when it's clockwise on screen (y pointing down).
+fn orient(a: FixedPoint, b: FixedPoint, c: FixedPoint) -> i64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/// `orient(a, b, p)` as a function of the pixel `p`, sampled at pixel centers and stepped a
+/// pixel at a time.
+#[derive(Clone, Copy)]
+struct Edge {
+    step_x: i64,
+    step_y: i64,
+    /// The value at the center of pixel (0, 0).
+    origin: i64,
+    /// Pixel centers exactly on the edge only count for top and left edges, so that triangles
+    /// sharing an edge don't both draw (or both skip) them.
+    bias: i64,
+}
+
+impl Edge {
+    fn new(a: FixedPoint, b: FixedPoint) -> Edge {
+        let dx = b.0 - a.0;
+        let dy = b.1 - a.1;
+
+        // with clockwise winding, top edges go right and left edges go up
+        let top_left = (dy == 0 && dx > 0) || dy < 0;
+
+        Edge {
+            step_x: -dy * SUBPIXEL_ONE,
+            step_y: dx * SUBPIXEL_ONE,
+            origin: orient(a, b, (SUBPIXEL_HALF, SUBPIXEL_HALF)),
+            bias: if top_left { 0 } else { -1 },
+        }
+    }
+
+    fn at(&self, x: i32, y: i32) -> i64 {
+        self.origin + x as i64 * self.step_x + y as i64 * self.step_y
+    }
+
+    fn covers(&self, w: i64) -> bool {
+        w + self.bias >= 0
+    }
+
+    /// How much the value changes from a pixel's center to a point `(x, y)` sixteenths of a
+    /// pixel away from it. Exact, as the steps are whole multiples of the sub-pixel grid.
+    fn sample_offset(&self, x: i64, y: i64) -> i64 {
+        (x * self.step_x + y * self.step_y) / 16
+    }
+}
+
+/// A strip of whole rows of the color and depth buffers, which one thread can draw into while
+/// others draw into the rest. Each pixel has `multisample.samples()` consecutive entries in
+/// both.
+pub struct Band<'a> {
+    pub color: &'a mut [Rgb24],
+    pub depth: &'a mut [f64],
+    /// The row the strip starts at.
+    pub top: i32,
+    pub width: i32,
+    pub multisample: Multisample,
+}
+
+impl<'a> Band<'a> {
+    /// Where the first sample of a pixel is.
+    pub fn offset(&self, point: Point2i) -> usize {
+        ((point.y - self.top) * self.width + point.x) as usize * self.multisample.samples()
+    }
+}
+
+/// Sorts triangles into the tiles their bounds overlap, as indices into `triangles` in
+/// submission order. Tiles are numbered row by row.
+pub fn bin_triangles<V>(triangles: &[Triangle<V>], width: i32, height: i32) -> Vec<Vec<usize>> {
+    let tiles_x = (width + TILE_SIZE - 1) / TILE_SIZE;
+    let tiles_y = (height + TILE_SIZE - 1) / TILE_SIZE;
+    let mut bins = vec![Vec::new(); (tiles_x * tiles_y) as usize];
+
+    for (i, triangle) in triangles.iter().enumerate() {
+        let bounds = &triangle.bounds;
+
+        // bounds are exclusive on the right and bottom
+        for tile_y in (bounds.top / TILE_SIZE)..((bounds.bottom - 1) / TILE_SIZE + 1) {
+            for tile_x in (bounds.left / TILE_SIZE)..((bounds.right - 1) / TILE_SIZE + 1) {
+                bins[(tile_y * tiles_x + tile_x) as usize].push(i);
+            }
+        }
+    }
+
+    bins
+}
+
+/// The pixels of a tile, cut short at the right and bottom edges of the frame.
+pub fn tile_rect(tile_x: i32, tile_y: i32, width: i32, height: i32) -> Rect {
+    Rect::new(Point2i::new(tile_x * TILE_SIZE, tile_y * TILE_SIZE),
+              Point2i::new(cmp::min((tile_x + 1) * TILE_SIZE, width),
+                           cmp::min((tile_y + 1) * TILE_SIZE, height)))
+}
+
+/// Fills in the part of a triangle that lies inside `clip`, which has to be within `band`.
+/// Every pixel only depends on the triangles drawn to it before, and coverage is worked out in
+/// exact integer arithmetic, so splitting a frame up and drawing each piece in submission order
+/// gives exactly the same image as drawing it whole.
+///
+/// Pixels are visited in 2x2 quads aligned to even coordinates, with coverage and depth for all
+/// four tested together at each of the band's sample positions.
+pub fn draw_triangle<S>(band: &mut Band,
+                        clip: Rect,
+                        shader: &S,
+                        uniforms: &Uniforms,
+                        interpolation: Interpolation,
+                        triangle: &Triangle<S::Varying>)
+    where S: Shader
+{
+    let rect = match triangle.bounds.intersect(clip) {
+        Some(rect) => rect,
+        None => return,
+    };
+
+    let edges = &triangle.edges;
+    let pattern = band.multisample.pattern();
+
+    // how far each sample is from the pixel center, in each edge function
+    let mut sample_steps = [[0; 3]; MAX_SAMPLES];
+    for (s, &(sx, sy)) in pattern.iter().enumerate() {
+        for i in 0..3 {
+            sample_steps[s][i] = edges[i].sample_offset(sx, sy);
+        }
+    }
+    let sample_steps = &sample_steps[..pattern.len()];
+
+    let left = rect.left & !1;
+    let top = rect.top & !1;
+
+    let mut row = [edges[0].at_quad(left, top),
+                   edges[1].at_quad(left, top),
+                   edges[2].at_quad(left, top)];
+
+    let mut y = top;
+    while y < rect.bottom {
+        let mut w = row;
+
+        let mut x = left;
+        while x < rect.right {
+            let inside = rect_mask(&rect, x, y);
+
+            // per lane, a bit for each sample the triangle covers
+            let mut coverage = [0u8; 4];
+            for (s, steps) in sample_steps.iter().enumerate() {
+                let mask = edges[0].coverage(&add_lanes(w[0], steps[0])) &
+                           edges[1].coverage(&add_lanes(w[1], steps[1])) &
+                           edges[2].coverage(&add_lanes(w[2], steps[2])) &
+                           inside;
+                for lane in 0..4 {
+                    coverage[lane] |= ((mask >> lane) & 1) << s;
+                }
+            }
+
+            if coverage != [0; 4] {
+                let quad = Quad::new(triangle, interpolation, band, &w, sample_steps,
+                                     Point2i::new(x, y), coverage);
+                quad.shade(band, shader, uniforms);
+            }
+
+            for i in 0..3 {
+                w[i] = add_lanes(w[i], edges[i].step_x * 2);
+            }
+            x += 2;
+        }
+
+        for i in 0..3 {
+            row[i] = add_lanes(row[i], edges[i].step_y * 2);
+        }
+        y += 2;
+    }
+}
+
+/// Offsets of the pixels in a quad, in lane order: top left, top right, bottom left, bottom
+/// right.
+const QUAD_X: [i32; 4] = [0, 1, 0, 1];
+const QUAD_Y: [i32; 4] = [0, 0, 1, 1];
+
+/// Per-pixel values for a quad, one lane each. Everything done to them is the same operation on
+/// all four lanes with no branches, which LLVM turns into vector instructions on stable Rust.
+type Lanes<T> = [T; 4];
+
+fn add_lanes(a: Lanes<i64>, b: i64) -> Lanes<i64> {
+    [a[0] + b, a[1] + b, a[2] + b, a[3] + b]
+}
+
+/// The lanes of the quad at `(x, y)` that fall inside `rect`, as a bit mask.
+fn rect_mask(rect: &Rect, x: i32, y: i32) -> u8 {
+    let mut mask = 0;
+    for lane in 0..4 {
+        let px = x + QUAD_X[lane];
+        let py = y + QUAD_Y[lane];
+        let inside = px >= rect.left && px < rect.right && py >= rect.top && py < rect.bottom;
+        mask |= (inside as u8) << lane;
+    }
+    mask
+}
+
+impl Edge {
+    fn at_quad(&self, x: i32, y: i32) -> Lanes<i64> {
+        let w = self.at(x, y);
+        [w, w + self.step_x, w + self.step_y, w + self.step_x + self.step_y]
+    }
+
+    fn coverage(&self, w: &Lanes<i64>) -> u8 {
+        let mut mask = 0;
+        for lane in 0..4 {
+            mask |= (self.covers(w[lane]) as u8) << lane;
+        }
+        mask
+    }
+}
+
+/// A 2x2 block of pixels on its way to the fragment shader. Varyings are interpolated at the
+/// centers of all four pixels, including ones outside the triangle, so that neighbouring lanes
+/// can be differenced for screen space derivatives (texture LOD, for one).
+struct Quad<V> {
+    x: i32,
+    y: i32,
+    /// Per lane, the samples that are covered and passed the depth test.
+    coverage: Lanes<u8>,
+    /// Per lane, the depth at each sample.
+    z: Lanes<[f64; MAX_SAMPLES]>,
+    varyings: Lanes<V>,
+}
+
+impl<V> Quad<V>
+    where V: Varying
+{
+    fn new(triangle: &Triangle<V>,
+           interpolation: Interpolation,
+           band: &Band,
+           w: &[Lanes<i64>; 3],
+           sample_steps: &[[i64; 3]],
+           pt: Point2i,
+           coverage: Lanes<u8>)
+           -> Quad<V> {
+        let (v0, v1, v2) = (triangle.vertices[0], triangle.vertices[1], triangle.vertices[2]);
+        let area = triangle.area as f64;
+        let depth_at = |w0: i64, w1: i64, w2: i64| {
+            (v0.position.z * w0 as f64 + v1.position.z * w1 as f64 +
+             v2.position.z * w2 as f64) / area
+        };
+
+        let mut bc = [(0.0, 0.0, 0.0); 4];
+        let mut offsets = [0; 4];
+        for lane in 0..4 {
+            bc[lane] = (w[0][lane] as f64 / area,
+                        w[1][lane] as f64 / area,
+                        w[2][lane] as f64 / area);
+            offsets[lane] = band.offset(Point2i::new(pt.x + QUAD_X[lane], pt.y + QUAD_Y[lane]));
+        }
+
+        // the depth test runs on all four lanes at once, sample by sample, and only then is
+        // masked by coverage
+        let mut z = [[0.0; MAX_SAMPLES]; 4];
+        let mut coverage = coverage;
+        for (s, steps) in sample_steps.iter().enumerate() {
+            let mut stored = [0.0; 4];
+            for lane in 0..4 {
+                z[lane][s] = depth_at(w[0][lane] + steps[0],
+                                      w[1][lane] + steps[1],
+                                      w[2][lane] + steps[2]);
+                // lanes past the right or bottom of the band are never covered, so it doesn't
+                // matter what they read, as long as they don't read past its end
+                stored[lane] = band.depth.get(offsets[lane] + s).cloned().unwrap_or(f64::MAX);
+            }
+
+            for lane in 0..4 {
+                let failed = (stored[lane] < z[lane][s]) as u8;
+                coverage[lane] &= !(failed << s);
+            }
+        }
+
+        let interpolate = |bc: (f64, f64, f64)| {
+            let varying_bc = match interpolation {
+                Interpolation::Perspective => {
+                    // screen space weights are linear in 1/w, not in the attributes themselves
+                    let w0 = bc.0 * v0.inv_w;
+                    let w1 = bc.1 * v1.inv_w;
+                    let w2 = bc.2 * v2.inv_w;
+                    let sum = w0 + w1 + w2;
+                    (w0 / sum, w1 / sum, w2 / sum)
+                }
+                Interpolation::Affine => bc,
+            };
+            V::barycentric(v0.varying, v1.varying, v2.varying, varying_bc)
+        };
+
+        Quad {
+            x: pt.x,
+            y: pt.y,
+            coverage: coverage,
+            z: z,
+            varyings: [interpolate(bc[0]),
+                       interpolate(bc[1]),
+                       interpolate(bc[2]),
+                       interpolate(bc[3])],
+        }
+    }
+
+    fn shade<S>(&self, band: &mut Band, shader: &S, uniforms: &Uniforms)
+        where S: Shader<Varying = V>
+    {
+        for lane in 0..4 {
+            let coverage = self.coverage[lane];
+            if coverage == 0 {
+                continue;
+            }
+
+            let pt = Point2i::new(self.x + QUAD_X[lane], self.y + QUAD_Y[lane]);
+            let offset = band.offset(pt);
+
+            // a depth only pass has no color to shade
+            if band.color.is_empty() {
+                for s in 0..MAX_SAMPLES {
+                    if coverage & (1 << s) != 0 {
+                        band.depth[offset + s] = self.z[lane][s];
+                    }
+                }
+                continue;
+            }
+
+            // once per pixel, however many of its samples are covered
+            if let Fragment::Color(color) = shader.fragment(uniforms, self.varyings[lane]) {
+                for s in 0..MAX_SAMPLES {
+                    if coverage & (1 << s) != 0 {
+                        band.depth[offset + s] = self.z[lane][s];
+                        band.color[offset + s] = color;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use rect::{Point2i, Rect};
+    use raster::{ScreenVertex, Triangle};
+
+    fn vertex(x: f64, y: f64) -> ScreenVertex<()> {
+        ScreenVertex {
+            position: Vector3::new(x, y, 0.5),
+            inv_w: 1.0,
+            varying: (),
+        }
+    }
+
+    fn triangle(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> Option<Triangle<()>> {
+        let target = Rect::new(Point2i::new(0, 0), Point2i::new(32, 32));
+        Triangle::new(vertex(a.0, a.1), vertex(b.0, b.1), vertex(c.0, c.1), target)
+    }
+
+    fn covers(triangle: &Triangle<()>, x: i32, y: i32) -> bool {
+        triangle.edges.iter().all(|edge| edge.covers(edge.at(x, y)))
+    }
+
+    /// Splits the square from (4, 4) to (20, 20) along a diagonal, which runs right through
+    /// the centers of the pixels along it, and checks that every pixel of the square is drawn
+    /// by exactly one of the halves, and nothing outside it by either.
+    fn assert_split_covers_once(first: Triangle<()>, second: Triangle<()>) {
+        for y in 0..32 {
+            for x in 0..32 {
+                let count = covers(&first, x, y) as u32 + covers(&second, x, y) as u32;
+                let inside = x >= 4 && x < 20 && y >= 4 && y < 20;
+                assert_eq!(count, inside as u32, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn shared_edges_are_drawn_once() {
+        let (top_left, top_right) = ((4.0, 4.0), (20.0, 4.0));
+        let (bottom_left, bottom_right) = ((4.0, 20.0), (20.0, 20.0));
+
+        assert_split_covers_once(triangle(top_left, top_right, bottom_right).unwrap(),
+                                 triangle(top_left, bottom_right, bottom_left).unwrap());
+        assert_split_covers_once(triangle(top_right, bottom_left, top_left).unwrap(),
+                                 triangle(top_right, bottom_right, bottom_left).unwrap());
+
+        // the other way round on screen
+        assert_split_covers_once(triangle(top_left, bottom_right, top_right).unwrap(),
+                                 triangle(top_left, bottom_left, bottom_right).unwrap());
+    }
+
+    #[test]
+    fn degenerate_triangles_are_dropped() {
+        assert!(triangle((1.0, 1.0), (5.0, 5.0), (10.0, 10.0)).is_none());
+        assert!(triangle((3.0, 8.0), (3.0, 2.0), (3.0, 30.0)).is_none());
+        assert!(triangle((3.0, 3.0), (3.0, 3.0), (8.0, 1.0)).is_none());
+        assert!(triangle((6.5, 6.5), (6.5, 6.5), (6.5, 6.5)).is_none());
+    }
+}
@@ -6,7 +6,7 @@ use std::cmp;
 pub type Point2i = Vector2<i32>;
 pub type Point3f = Vector3<f64>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Rect {
     pub left: i32,
     pub top: i32,
@@ -0,0 +1,326 @@
+extern crate cgmath;
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use cgmath::Vector3;
+use cgmath::Matrix4;
+use cgmath::Quaternion;
+use cgmath::SquareMatrix;
+
+use camera::Camera;
+use light::Light;
+use material::Material;
+use mesh::Mesh;
+use transform::Transform;
+
+/// Identifies a node within its `Scene`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// An element of the scene graph: a transform relative to its parent, optionally with a mesh
+/// attached. Transforms are changed through the `Scene` so that the cached world transforms
+/// below the node can be invalidated.
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub mesh: Option<Mesh>,
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: Cell<Matrix4<f64>>,
+    dirty: Cell<bool>,
+}
+
+impl Node {
+    fn new(name: &str, parent: Option<NodeId>) -> Node {
+        Node {
+            name: name.to_owned(),
+            mesh: None,
+            transform: Transform::identity(),
+            parent: parent,
+            children: Vec::new(),
+            world: Cell::new(Matrix4::identity()),
+            dirty: Cell::new(true),
+        }
+    }
+
+    /// Placement relative to the parent.
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// The transform from this node's space into its parent's.
+    pub fn local_transform(&self) -> Matrix4<f64> {
+        self.transform.to_matrix()
+    }
+}
+
+/// Everything that goes into a frame: a hierarchy of nodes carrying meshes, the lights shining
+/// on them, a library of named materials for meshes to share, and the cameras to look at it all
+/// through.
+#[derive(Debug)]
+pub struct Scene {
+    // removed nodes leave a hole so that ids stay valid
+    nodes: Vec<Option<Node>>,
+    roots: Vec<NodeId>,
+    lights: Vec<Light>,
+    materials: HashMap<String, Rc<Material>>,
+    cameras: Vec<Camera>,
+    active_camera: usize,
+}
+
+impl Scene {
+    /// A scene has to be seen through something, so it starts out with one camera.
+    pub fn new(camera: Camera) -> Scene {
+        Scene {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            lights: Vec::new(),
+            materials: HashMap::new(),
+            cameras: vec![camera],
+            active_camera: 0,
+        }
+    }
+
+    /// Adds an empty node, at the root of the scene if it has no parent.
+    pub fn add_node(&mut self, name: &str, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Some(Node::new(name, parent)));
+
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+
+        id
+    }
+
+    /// Adds a mesh on a new node named after it.
+    pub fn add_mesh(&mut self, mesh: Mesh, parent: Option<NodeId>) -> NodeId {
+        let name = mesh.name.clone();
+        let id = self.add_node(&name, parent);
+        self.node_mut(id).mesh = Some(mesh);
+        id
+    }
+
+    /// Removes a node along with everything below it, and returns the node's own mesh.
+    pub fn remove_node(&mut self, id: NodeId) -> Option<Mesh> {
+        match self.node(id).parent {
+            Some(parent) => self.node_mut(parent).children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id),
+        }
+
+        self.take_subtree(id)
+    }
+
+    fn take_subtree(&mut self, id: NodeId) -> Option<Mesh> {
+        let node = self.nodes[id.0].take().expect("node was already removed");
+        for &child in node.children.iter() {
+            self.take_subtree(child);
+        }
+        node.mesh
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        self.nodes[id.0].as_ref().expect("node was removed")
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.nodes[id.0].as_mut().expect("node was removed")
+    }
+
+    /// The first node called `name`.
+    pub fn find_node(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .position(|node| node.as_ref().map_or(false, |node| node.name == name))
+            .map(NodeId)
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+        self.node_mut(id).transform = transform;
+        self.mark_dirty(id);
+    }
+
+    pub fn set_position(&mut self, id: NodeId, position: Vector3<f64>) {
+        self.node_mut(id).transform.translation = position;
+        self.mark_dirty(id);
+    }
+
+    pub fn set_rotation(&mut self, id: NodeId, rotation: Quaternion<f64>) {
+        self.node_mut(id).transform.rotation = rotation;
+        self.mark_dirty(id);
+    }
+
+    pub fn set_scale(&mut self, id: NodeId, scale: Vector3<f64>) {
+        self.node_mut(id).transform.scale = scale;
+        self.mark_dirty(id);
+    }
+
+    /// Moves a node (and everything below it) under a new parent, or to the root of the scene.
+    /// The node keeps its local transform.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            assert!(ancestor_id != id, "a node can't be its own ancestor");
+            ancestor = self.node(ancestor_id).parent;
+        }
+
+        match self.node(id).parent {
+            Some(old_parent) => self.node_mut(old_parent).children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id),
+        }
+        match parent {
+            Some(new_parent) => self.node_mut(new_parent).children.push(id),
+            None => self.roots.push(id),
+        }
+
+        self.node_mut(id).parent = parent;
+        self.mark_dirty(id);
+    }
+
+    fn mark_dirty(&self, id: NodeId) {
+        let node = self.node(id);
+
+        // a node's world transform is only ever cleaned after its parent's, so if this one is
+        // already dirty then so is everything below it
+        if node.dirty.get() {
+            return;
+        }
+
+        node.dirty.set(true);
+        for &child in node.children.iter() {
+            self.mark_dirty(child);
+        }
+    }
+
+    /// The transform from a node's space into world space.
+    pub fn world_transform(&self, id: NodeId) -> Matrix4<f64> {
+        let node = self.node(id);
+
+        if node.dirty.get() {
+            let parent_world = match node.parent {
+                Some(parent) => self.world_transform(parent),
+                None => Matrix4::identity(),
+            };
+            node.world.set(parent_world * node.local_transform());
+            node.dirty.set(false);
+        }
+
+        node.world.get()
+    }
+
+    /// Every mesh in the scene along with its world transform, depth first with parents before
+    /// their children.
+    pub fn mesh_instances(&self) -> Vec<(Matrix4<f64>, &Mesh)> {
+        let mut instances = Vec::new();
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().cloned().collect();
+
+        while let Some(id) = stack.pop() {
+            let node = self.node(id);
+            if let Some(ref mesh) = node.mesh {
+                instances.push((self.world_transform(id), mesh));
+            }
+            stack.extend(node.children.iter().rev().cloned());
+        }
+
+        instances
+    }
+
+    /// The first mesh called `name`.
+    pub fn mesh(&self, name: &str) -> Option<&Mesh> {
+        self.nodes
+            .iter()
+            .filter_map(|node| node.as_ref().and_then(|node| node.mesh.as_ref()))
+            .find(|mesh| mesh.name == name)
+    }
+
+    pub fn mesh_mut(&mut self, name: &str) -> Option<&mut Mesh> {
+        self.nodes
+            .iter_mut()
+            .filter_map(|node| node.as_mut().and_then(|node| node.mesh.as_mut()))
+            .find(|mesh| mesh.name == name)
+    }
+
+    /// Detaches and returns the first mesh called `name`. Its node stays behind, along with
+    /// anything attached below it.
+    pub fn remove_mesh(&mut self, name: &str) -> Option<Mesh> {
+        for node in self.nodes.iter_mut() {
+            if let Some(ref mut node) = *node {
+                if node.mesh.as_ref().map_or(false, |mesh| mesh.name == name) {
+                    return node.mesh.take();
+                }
+            }
+        }
+        None
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn lights_mut(&mut self) -> &mut [Light] {
+        &mut self.lights
+    }
+
+    /// Returns the light's index, for `remove_light`.
+    pub fn add_light(&mut self, light: Light) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn remove_light(&mut self, index: usize) -> Light {
+        self.lights.remove(index)
+    }
+
+    /// Adds a material to the library, replacing any with the same name, and returns a handle
+    /// for `Mesh::set_material`.
+    pub fn add_material(&mut self, name: &str, material: Material) -> Rc<Material> {
+        let material = Rc::new(material);
+        self.materials.insert(name.to_owned(), material.clone());
+        material
+    }
+
+    pub fn material(&self, name: &str) -> Option<Rc<Material>> {
+        self.materials.get(name).cloned()
+    }
+
+    /// Meshes that use the material keep their handle to it.
+    pub fn remove_material(&mut self, name: &str) -> Option<Rc<Material>> {
+        self.materials.remove(name)
+    }
+
+    pub fn cameras(&self) -> &[Camera] {
+        &self.cameras
+    }
+
+    /// Returns the camera's index, for `set_active_camera`.
+    pub fn add_camera(&mut self, camera: Camera) -> usize {
+        self.cameras.push(camera);
+        self.cameras.len() - 1
+    }
+
+    /// The camera the scene is rendered through.
+    pub fn camera(&self) -> &Camera {
+        &self.cameras[self.active_camera]
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.cameras[self.active_camera]
+    }
+
+    pub fn set_active_camera(&mut self, index: usize) {
+        assert!(index < self.cameras.len());
+        self.active_camera = index;
+    }
+}
@@ -0,0 +1,20 @@
+use cgmath::Vector4;
+
+use bitmap::pixel_format::Rgb24;
+use shader::{Shader, Uniforms, VertexInput, Fragment};
+
+/// Positions only, for passes that just fill in a depth buffer, like rendering shadow maps.
+/// The device never runs the fragment stage of a depth only pass.
+pub struct DepthShader;
+
+impl Shader for DepthShader {
+    type Varying = ();
+
+    fn vertex(&self, uniforms: &Uniforms, input: &VertexInput) -> (Vector4<f64>, Self::Varying) {
+        (uniforms.mvp * input.position.extend(1.0), ())
+    }
+
+    fn fragment(&self, _: &Uniforms, _: Self::Varying) -> Fragment {
+        Fragment::Color(Rgb24::default())
+    }
+}
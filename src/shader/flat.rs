@@ -0,0 +1,48 @@
+use cgmath::Vector3;
+use cgmath::Vector4;
+use cgmath::EuclideanVector;
+
+use bitmap::pixel_format::Rgb24;
+use shader::{Shader, Uniforms, VertexInput, Fragment, Flat};
+use shader::lighting;
+use shader::lighting::{LightSources, Specular};
+
+/// One color per face, lit at its provoking vertex with the face's own normal.
+pub struct FlatShader;
+
+impl Shader for FlatShader {
+    /// The world space position out of `vertex`, and the face's color once `light_face` has
+    /// lit it.
+    type Varying = Flat<Vector3<f64>>;
+
+    fn vertex(&self, uniforms: &Uniforms, input: &VertexInput) -> (Vector4<f64>, Self::Varying) {
+        let world_position = (uniforms.model * input.position.extend(1.0)).truncate();
+        (uniforms.mvp * input.position.extend(1.0), Flat(world_position))
+    }
+
+    fn fragment(&self, _: &Uniforms, Flat(color): Self::Varying) -> Fragment {
+        Fragment::Color(Rgb24::from(color))
+    }
+
+    fn lights_faces(&self) -> bool {
+        true
+    }
+
+    fn light_face(&self,
+                  uniforms: &Uniforms,
+                  Flat(world_position): Self::Varying,
+                  normal: Vector3<f64>)
+                  -> Self::Varying {
+        let world_normal = (uniforms.normal_matrix * normal).normalize();
+
+        let color = lighting::shade(uniforms.material,
+                                    uniforms.material.diffuse,
+                                    world_position,
+                                    world_normal,
+                                    (uniforms.eye - world_position).normalize(),
+                                    LightSources::unshadowed(uniforms.light_sources.lights),
+                                    Specular::BlinnPhong);
+
+        Flat(color)
+    }
+}
@@ -0,0 +1,34 @@
+use cgmath::Vector3;
+use cgmath::Vector4;
+use cgmath::EuclideanVector;
+
+use bitmap::pixel_format::Rgb24;
+use shader::{Shader, Uniforms, VertexInput, Fragment};
+use shader::lighting;
+use shader::lighting::{LightSources, Specular};
+
+/// Lit at the vertices, with the colors interpolated across the face.
+pub struct GouraudShader;
+
+impl Shader for GouraudShader {
+    type Varying = Vector3<f64>;
+
+    fn vertex(&self, uniforms: &Uniforms, input: &VertexInput) -> (Vector4<f64>, Self::Varying) {
+        let world_position = (uniforms.model * input.position.extend(1.0)).truncate();
+        let world_normal = (uniforms.normal_matrix * input.normal).normalize();
+
+        let color = lighting::shade(uniforms.material,
+                                    uniforms.material.diffuse,
+                                    world_position,
+                                    world_normal,
+                                    (uniforms.eye - world_position).normalize(),
+                                    LightSources::unshadowed(uniforms.light_sources.lights),
+                                    Specular::BlinnPhong);
+
+        (uniforms.mvp * input.position.extend(1.0), color)
+    }
+
+    fn fragment(&self, _: &Uniforms, color: Self::Varying) -> Fragment {
+        Fragment::Color(Rgb24::from(color))
+    }
+}
@@ -0,0 +1,94 @@
+use cgmath::Vector3;
+use cgmath::Vector;
+use cgmath::EuclideanVector;
+
+use light::Light;
+use material::Material;
+use math::modulate;
+use shadow;
+use shadow::ShadowMap;
+
+/// The lights in a scene, and the shadow maps that say where each of them is blocked.
+#[derive(Clone, Copy)]
+pub struct LightSources<'a> {
+    pub lights: &'a [Light],
+    /// One for each of `lights` that casts shadows, in the same order; can be empty.
+    pub shadow_maps: &'a [Option<ShadowMap>],
+}
+
+impl<'a> LightSources<'a> {
+    pub fn new(lights: &'a [Light], shadow_maps: &'a [Option<ShadowMap>]) -> LightSources<'a> {
+        LightSources {
+            lights: lights,
+            shadow_maps: shadow_maps,
+        }
+    }
+
+    /// Lights that don't cast shadows.
+    pub fn unshadowed(lights: &'a [Light]) -> LightSources<'a> {
+        LightSources::new(lights, &[])
+    }
+}
+
+/// How the specular highlight is computed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Specular {
+    /// Reflected light direction against the view direction.
+    Phong,
+    /// Half vector against the normal; cheaper and better behaved at grazing angles.
+    BlinnPhong,
+}
+
+/// Ambient light plus the diffuse and specular terms of every light, less whatever the shadow
+/// maps say is blocked. `normal` and `to_eye` are normalized, and everything is in world
+/// space.
+pub fn shade(material: &Material,
+             diffuse_color: Vector3<f64>,
+             position: Vector3<f64>,
+             normal: Vector3<f64>,
+             to_eye: Vector3<f64>,
+             sources: LightSources,
+             specular: Specular)
+             -> Vector3<f64> {
+    let mut color = modulate(material.ambient, diffuse_color);
+
+    for (i, light) in sources.lights.iter().enumerate() {
+        let (to_light, radiance) = light.illuminate(position);
+
+        let diffuse_factor = normal.dot(to_light);
+        if diffuse_factor <= 0.0 {
+            continue;
+        }
+
+        let visibility = shadow::visibility(sources.shadow_maps, i, position, diffuse_factor);
+        if visibility <= 0.0 {
+            continue;
+        }
+
+        let specular_factor = match specular {
+            Specular::Phong => {
+                let reflected = normal * (2.0 * diffuse_factor) - to_light;
+                reflected.dot(to_eye).max(0.0).powf(material.shininess)
+            }
+            Specular::BlinnPhong => {
+                let half = (to_light + to_eye).normalize();
+                normal.dot(half).max(0.0).powf(material.shininess)
+            }
+        };
+
+        color = color +
+                modulate(radiance * visibility,
+                         diffuse_color * diffuse_factor + material.specular * specular_factor);
+    }
+
+    color
+}
+
+/// The diffuse light arriving at a surface from every light, before the material gets a say.
+/// Shadows aren't taken into account.
+pub fn irradiance(position: Vector3<f64>, normal: Vector3<f64>, lights: &[Light]) -> Vector3<f64> {
+    lights.iter().fold(Vector3::zero(), |sum, light| {
+        let (to_light, radiance) = light.illuminate(position);
+        sum + radiance * normal.dot(to_light).max(0.0)
+    })
+}
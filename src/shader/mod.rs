@@ -0,0 +1,189 @@
+pub mod depth;
+pub mod flat;
+pub mod gouraud;
+pub mod lighting;
+pub mod phong;
+pub mod texture;
+
+pub use shader::depth::DepthShader;
+pub use shader::flat::FlatShader;
+pub use shader::gouraud::GouraudShader;
+pub use shader::phong::PhongShader;
+pub use shader::texture::TextureShader;
+
+use cgmath::Vector2;
+use cgmath::Vector3;
+use cgmath::Vector4;
+use cgmath::Matrix3;
+use cgmath::Matrix4;
+
+use bitmap::pixel_format::Rgb24;
+use material::Material;
+use rect::Point3f;
+use shader::lighting::LightSources;
+
+/// Per-draw values shared by every vertex and fragment, set up by the `Device` for each mesh.
+pub struct Uniforms<'a> {
+    pub model: Matrix4<f64>,
+    pub view: Matrix4<f64>,
+    pub projection: Matrix4<f64>,
+    /// `projection * view * model`
+    pub mvp: Matrix4<f64>,
+    /// Takes model space normals to world space.
+    pub normal_matrix: Matrix3<f64>,
+    /// Camera position in world space.
+    pub eye: Vector3<f64>,
+    /// Shadows are only worth looking up per fragment, as a vertex or a face lit in shadow
+    /// would smear the shadow's edge right across its triangles. The built-in shaders that
+    /// light vertices or faces ignore the shadow maps.
+    pub light_sources: LightSources<'a>,
+    pub material: &'a Material,
+}
+
+/// What the vertex stage gets to see of a mesh vertex.
+pub struct VertexInput {
+    pub position: Point3f,
+    pub normal: Vector3<f64>,
+    pub uv: Vector2<f64>,
+}
+
+pub enum Fragment {
+    Color(Rgb24),
+    /// Leave the pixel (and its depth) untouched.
+    Discard,
+}
+
+/// A programmable shading model, in the spirit of tinyrenderer's `IShader`. Fragments are shaded
+/// on several threads at once, hence `Sync`.
+pub trait Shader: Sync {
+    type Varying: Varying;
+
+    /// Transforms a vertex, returning its clip space position and the varyings to be
+    /// interpolated across the triangle.
+    fn vertex(&self, uniforms: &Uniforms, input: &VertexInput) -> (Vector4<f64>, Self::Varying);
+
+    /// Shades a single pixel given the interpolated varyings.
+    fn fragment(&self, uniforms: &Uniforms, varying: Self::Varying) -> Fragment;
+
+    /// Whether the shader lights whole faces, in `light_face`. Its vertices are then shared
+    /// between every face that meets at them, whatever their normals, so `vertex` shouldn't
+    /// look at `VertexInput::normal`.
+    fn lights_faces(&self) -> bool {
+        false
+    }
+
+    /// Gives every corner of a triangle the same varyings, worked out from what `vertex` gave
+    /// its provoking vertex and the model space normal of the mesh face it came from. Only
+    /// called if `lights_faces` says so.
+    fn light_face(&self,
+                  _uniforms: &Uniforms,
+                  provoking: Self::Varying,
+                  _normal: Vector3<f64>)
+                  -> Self::Varying {
+        provoking
+    }
+}
+
+/// Data passed from the vertex to the fragment stage. The rasterizer only ever needs to take
+/// weighted sums of these, so anything that behaves like a vector will do.
+pub trait Varying: Copy + Send + Sync {
+    fn plus(self, other: Self) -> Self;
+    fn scale(self, factor: f64) -> Self;
+
+    fn barycentric(a: Self, b: Self, c: Self, bc: (f64, f64, f64)) -> Self {
+        a.scale(bc.0).plus(b.scale(bc.1)).plus(c.scale(bc.2))
+    }
+
+    fn lerp(a: Self, b: Self, t: f64) -> Self {
+        a.scale(1.0 - t).plus(b.scale(t))
+    }
+}
+
+/// A varying that isn't interpolated: every fragment gets the value from the first (provoking)
+/// vertex of its triangle, like GLSL's `flat` qualifier.
+#[derive(Clone, Copy)]
+pub struct Flat<T>(pub T);
+
+impl<T> Varying for Flat<T>
+    where T: Copy + Send + Sync
+{
+    fn plus(self, _: Flat<T>) -> Flat<T> {
+        self
+    }
+
+    fn scale(self, _: f64) -> Flat<T> {
+        self
+    }
+}
+
+impl Varying for () {
+    fn plus(self, _: ()) {}
+    fn scale(self, _: f64) {}
+}
+
+impl Varying for f64 {
+    fn plus(self, other: f64) -> f64 {
+        self + other
+    }
+
+    fn scale(self, factor: f64) -> f64 {
+        self * factor
+    }
+}
+
+impl Varying for Vector2<f64> {
+    fn plus(self, other: Vector2<f64>) -> Vector2<f64> {
+        self + other
+    }
+
+    fn scale(self, factor: f64) -> Vector2<f64> {
+        self * factor
+    }
+}
+
+impl Varying for Vector3<f64> {
+    fn plus(self, other: Vector3<f64>) -> Vector3<f64> {
+        self + other
+    }
+
+    fn scale(self, factor: f64) -> Vector3<f64> {
+        self * factor
+    }
+}
+
+impl Varying for Vector4<f64> {
+    fn plus(self, other: Vector4<f64>) -> Vector4<f64> {
+        self + other
+    }
+
+    fn scale(self, factor: f64) -> Vector4<f64> {
+        self * factor
+    }
+}
+
+impl<A, B> Varying for (A, B)
+    where A: Varying,
+          B: Varying
+{
+    fn plus(self, other: (A, B)) -> (A, B) {
+        (self.0.plus(other.0), self.1.plus(other.1))
+    }
+
+    fn scale(self, factor: f64) -> (A, B) {
+        (self.0.scale(factor), self.1.scale(factor))
+    }
+}
+
+impl<A, B, C> Varying for (A, B, C)
+    where A: Varying,
+          B: Varying,
+          C: Varying
+{
+    fn plus(self, other: (A, B, C)) -> (A, B, C) {
+        (self.0.plus(other.0), self.1.plus(other.1), self.2.plus(other.2))
+    }
+
+    fn scale(self, factor: f64) -> (A, B, C) {
+        (self.0.scale(factor), self.1.scale(factor), self.2.scale(factor))
+    }
+}
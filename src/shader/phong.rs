@@ -0,0 +1,55 @@
+use cgmath::Vector2;
+use cgmath::Vector3;
+use cgmath::Vector4;
+use cgmath::EuclideanVector;
+
+use bitmap::pixel_format::Rgb24;
+use math::modulate;
+use shader::{Shader, Uniforms, VertexInput, Fragment};
+use shader::lighting;
+use shader::lighting::Specular;
+
+/// Per-pixel lighting: normals and positions are interpolated and lit in the fragment stage.
+pub struct PhongShader {
+    pub specular: Specular,
+}
+
+impl PhongShader {
+    pub fn phong() -> PhongShader {
+        PhongShader { specular: Specular::Phong }
+    }
+
+    pub fn blinn_phong() -> PhongShader {
+        PhongShader { specular: Specular::BlinnPhong }
+    }
+}
+
+impl Shader for PhongShader {
+    /// World space position and normal, and texture coordinates.
+    type Varying = (Vector3<f64>, Vector3<f64>, Vector2<f64>);
+
+    fn vertex(&self, uniforms: &Uniforms, input: &VertexInput) -> (Vector4<f64>, Self::Varying) {
+        let world_position = (uniforms.model * input.position.extend(1.0)).truncate();
+        let world_normal = uniforms.normal_matrix * input.normal;
+
+        (uniforms.mvp * input.position.extend(1.0), (world_position, world_normal, input.uv))
+    }
+
+    fn fragment(&self, uniforms: &Uniforms, (position, normal, uv): Self::Varying) -> Fragment {
+        let material = uniforms.material;
+        let diffuse_color = match material.diffuse_map {
+            Some(ref texture) => modulate(material.diffuse, texture.sample(uv).to_vec()),
+            None => material.diffuse,
+        };
+
+        let color = lighting::shade(material,
+                                    diffuse_color,
+                                    position,
+                                    normal.normalize(),
+                                    (uniforms.eye - position).normalize(),
+                                    uniforms.light_sources,
+                                    self.specular);
+
+        Fragment::Color(Rgb24::from(color))
+    }
+}
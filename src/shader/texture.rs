@@ -0,0 +1,40 @@
+use cgmath::Vector2;
+use cgmath::Vector3;
+use cgmath::Vector4;
+use cgmath::EuclideanVector;
+
+use bitmap::pixel_format::Rgb24;
+use math::modulate;
+use shader::{Shader, Uniforms, VertexInput, Fragment};
+use shader::lighting;
+
+/// Samples the material's diffuse map and modulates it by diffuse light computed at the
+/// vertices, without shadows.
+pub struct TextureShader;
+
+impl Shader for TextureShader {
+    type Varying = (Vector2<f64>, Vector3<f64>);
+
+    fn vertex(&self, uniforms: &Uniforms, input: &VertexInput) -> (Vector4<f64>, Self::Varying) {
+        let world_position = (uniforms.model * input.position.extend(1.0)).truncate();
+        let world_normal = (uniforms.normal_matrix * input.normal).normalize();
+        let irradiance = lighting::irradiance(world_position,
+                                              world_normal,
+                                              uniforms.light_sources.lights);
+
+        (uniforms.mvp * input.position.extend(1.0), (input.uv, irradiance))
+    }
+
+    fn fragment(&self, uniforms: &Uniforms, (uv, irradiance): Self::Varying) -> Fragment {
+        let material = uniforms.material;
+        let texture = match material.diffuse_map {
+            Some(ref texture) => texture,
+            None => return Fragment::Discard,
+        };
+
+        let diffuse_color = modulate(material.diffuse, texture.sample(uv).to_vec());
+        let color = modulate(material.ambient + irradiance, diffuse_color);
+
+        Fragment::Color(Rgb24::from(color))
+    }
+}
@@ -0,0 +1,206 @@
+extern crate cgmath;
+
+use std::f64;
+use std::f64::consts::PI;
+
+use cgmath::EuclideanVector;
+use cgmath::Vector2;
+use cgmath::Vector3;
+use cgmath::Matrix4;
+use cgmath::Point;
+use cgmath::Point3;
+
+use bitmap::Bitmap;
+use camera::{Camera, Projection};
+use light::Light;
+use mesh::Mesh;
+
+/// How shadow maps are made and looked up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    /// Width and height of each map, in texels.
+    pub size: u32,
+    /// How much further from the light than the nearest thing in the map a surface has to be
+    /// to count as shadowed, in the map's depth units. Too little and surfaces shadow
+    /// themselves in stripes (acne); too much and shadows come loose from their casters.
+    pub depth_bias: f64,
+    /// Extra bias for surfaces at a grazing angle to the light, whose depth changes the most
+    /// across a texel of the map.
+    pub slope_bias: f64,
+    /// Percentage closer filtering: every lookup averages the depth test over a square of
+    /// texels this far out from the nearest one, which softens the edges of shadows. 0 gives
+    /// hard edges.
+    pub pcf_radius: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> ShadowSettings {
+        ShadowSettings {
+            size: 1024,
+            depth_bias: 0.001,
+            slope_bias: 0.004,
+            pcf_radius: 1,
+        }
+    }
+}
+
+/// Depth from a light's point of view, for telling whether something else is between a
+/// surface and the light.
+pub struct ShadowMap {
+    camera: Camera,
+    /// World space to the light's clip space.
+    view_projection: Matrix4<f64>,
+    depth: Bitmap<f64>,
+    settings: ShadowSettings,
+}
+
+impl ShadowMap {
+    /// Wraps a depth buffer that's been rendered through `camera`.
+    pub fn new(camera: Camera, depth: Bitmap<f64>, settings: ShadowSettings) -> ShadowMap {
+        ShadowMap {
+            view_projection: camera.view_projection_matrix(),
+            camera: camera,
+            depth: depth,
+            settings: settings,
+        }
+    }
+
+    /// A view from `light` that takes in everything within `radius` of `center`, which is
+    /// all that can cast or receive a shadow. Point lights shine every way at once and would
+    /// need six maps, so they don't get one.
+    pub fn light_camera(light: &Light, center: Point3<f64>, radius: f64) -> Option<Camera> {
+        match *light {
+            Light::Directional { direction, .. } => {
+                let camera = Camera {
+                    position: center + direction * (-radius * 2.0),
+                    target: center,
+                    up: up_for(direction),
+                    projection: Projection::orthographic(radius * 2.0, 1.0, radius, radius * 3.0),
+                };
+                Some(camera)
+            }
+            Light::Spot { position, direction, outer_angle, .. } => {
+                let position = Point3::from_vec(position);
+                let distance = (center - position).length();
+
+                // keep the depth range tight around the scene, for precision
+                let far = distance + radius;
+                let near = (distance - radius).max(far * 0.001);
+                let fovy = (outer_angle.s * 2.0).min(PI * 0.9);
+
+                let camera = Camera {
+                    position: position,
+                    target: position + direction,
+                    up: up_for(direction),
+                    projection: Projection::perspective(cgmath::rad(fovy), 1.0, near, far),
+                };
+                Some(camera)
+            }
+            Light::Point { .. } => None,
+        }
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn depth(&self) -> &Bitmap<f64> {
+        &self.depth
+    }
+
+    /// Takes the depth buffer back, to be drawn into again.
+    pub fn into_depth(self) -> Bitmap<f64> {
+        self.depth
+    }
+
+    /// How much of the light reaches a world space `position`, from 0 in full shadow to 1.
+    /// `cos_angle` is the cosine of the angle between the surface normal and the direction
+    /// towards the light. Anything outside the map is lit.
+    pub fn visibility(&self, position: Vector3<f64>, cos_angle: f64) -> f64 {
+        let clip = self.view_projection * position.extend(1.0);
+        if clip.w <= 0.0 {
+            return 1.0;
+        }
+
+        let ndc = clip.truncate() / clip.w;
+        if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 || ndc.z > 1.0 {
+            return 1.0;
+        }
+
+        // the same mapping as `Viewport::transform`, with texel centers on whole numbers
+        let width = self.depth.width() as f64;
+        let height = self.depth.height() as f64;
+        let x = ((ndc.x + 1.0) * 0.5 * width - 0.5).round() as i64;
+        let y = ((1.0 - ndc.y) * 0.5 * height - 0.5).round() as i64;
+
+        let settings = &self.settings;
+        let depth = (ndc.z + 1.0) * 0.5 -
+                    (settings.depth_bias + settings.slope_bias * (1.0 - cos_angle.max(0.0)));
+
+        let radius = settings.pcf_radius as i64;
+        let mut lit = 0;
+        for dy in -radius..(radius + 1) {
+            for dx in -radius..(radius + 1) {
+                if depth <= self.depth_at(x + dx, y + dy) {
+                    lit += 1;
+                }
+            }
+        }
+
+        let taps = (radius * 2 + 1) * (radius * 2 + 1);
+        lit as f64 / taps as f64
+    }
+
+    /// Clamped to the edges of the map.
+    fn depth_at(&self, x: i64, y: i64) -> f64 {
+        let x = x.max(0).min(self.depth.width() as i64 - 1) as u32;
+        let y = y.max(0).min(self.depth.height() as i64 - 1) as u32;
+        self.depth.get_pixel(Vector2::new(x, y))
+    }
+}
+
+/// How much of light number `index` reaches `position`, if it has a shadow map.
+pub fn visibility(shadow_maps: &[Option<ShadowMap>],
+                  index: usize,
+                  position: Vector3<f64>,
+                  cos_angle: f64)
+                  -> f64 {
+    match shadow_maps.get(index) {
+        Some(&Some(ref shadow_map)) => shadow_map.visibility(position, cos_angle),
+        _ => 1.0,
+    }
+}
+
+/// A sphere around every vertex of every mesh, in world space; `None` if there are no
+/// vertices at all.
+pub fn bounding_sphere(instances: &[(Matrix4<f64>, &Mesh)]) -> Option<(Point3<f64>, f64)> {
+    let mut min = Vector3::new(f64::MAX, f64::MAX, f64::MAX);
+    let mut max = Vector3::new(f64::MIN, f64::MIN, f64::MIN);
+    let mut empty = true;
+
+    for &(world_mat, mesh) in instances.iter() {
+        for vertex in mesh.vertices.iter() {
+            let p = (world_mat * vertex.extend(1.0)).truncate();
+            min = Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+            empty = false;
+        }
+    }
+
+    if empty {
+        return None;
+    }
+
+    // never quite zero, so that a single point still gets a valid projection
+    let radius = ((max - min).length() / 2.0).max(1e-3);
+    Some((Point3::from_vec((min + max) / 2.0), radius))
+}
+
+/// An up vector that isn't parallel to `direction`.
+fn up_for(direction: Vector3<f64>) -> Vector3<f64> {
+    if direction.normalize().y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
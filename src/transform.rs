@@ -0,0 +1,129 @@
+extern crate cgmath;
+
+use cgmath::Vector;
+use cgmath::EuclideanVector;
+use cgmath::Vector3;
+use cgmath::Matrix3;
+use cgmath::Matrix4;
+use cgmath::Quaternion;
+use cgmath::Rad;
+use cgmath::Rotation;
+use cgmath::Rotation3;
+
+/// Placement of a node relative to its parent: scaled first, then rotated, then translated.
+/// Rotations are kept as quaternions so that animating them doesn't run into gimbal lock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f64>,
+    pub rotation: Quaternion<f64>,
+    pub scale: Vector3<f64>,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            translation: Vector3::zero(),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn from_translation(translation: Vector3<f64>) -> Transform {
+        Transform { translation: translation, ..Transform::identity() }
+    }
+
+    pub fn with_rotation(self, rotation: Quaternion<f64>) -> Transform {
+        Transform { rotation: rotation, ..self }
+    }
+
+    /// Rotation from Euler angles, in radians, applied in the same order as
+    /// `Matrix3::from_euler`.
+    pub fn with_euler(self, x: Rad<f64>, y: Rad<f64>, z: Rad<f64>) -> Transform {
+        Transform { rotation: Quaternion::from(Matrix3::from_euler(x, y, z)), ..self }
+    }
+
+    pub fn with_scale(self, scale: Vector3<f64>) -> Transform {
+        Transform { scale: scale, ..self }
+    }
+
+    pub fn with_uniform_scale(self, scale: f64) -> Transform {
+        self.with_scale(Vector3::new(scale, scale, scale))
+    }
+
+    /// Turns the transform so that its front (+z, the way imported models face) points at
+    /// `target`, with its top as close to `up` as it can get. Both are in the parent's space.
+    pub fn look_at(self, target: Vector3<f64>, up: Vector3<f64>) -> Transform {
+        let front = (target - self.translation).normalize();
+        let right = up.cross(front).normalize();
+        let up = front.cross(right);
+
+        Transform { rotation: Quaternion::from(Matrix3::from_cols(right, up, front)), ..self }
+    }
+
+    /// Adds a rotation of `angle` around `axis`, in the parent's space, on top of the current
+    /// one.
+    pub fn rotate_around_axis(self, axis: Vector3<f64>, angle: Rad<f64>) -> Transform {
+        let rotation = Quaternion::from_axis_angle(axis.normalize(), angle);
+        Transform { rotation: (rotation * self.rotation).normalize(), ..self }
+    }
+
+    /// Blends towards `other`: translation and scale linearly, rotation along the shortest arc.
+    pub fn slerp(&self, other: &Transform, amount: f64) -> Transform {
+        // q and -q are the same rotation, but cgmath's slerp takes whichever way round the
+        // signs say, which can be the long way
+        let target = if self.rotation.dot(other.rotation) < 0.0 {
+            -other.rotation
+        } else {
+            other.rotation
+        };
+
+        Transform {
+            translation: self.translation + (other.translation - self.translation) * amount,
+            rotation: self.rotation.slerp(target, amount),
+            scale: self.scale + (other.scale - self.scale) * amount,
+        }
+    }
+
+    /// Where a point in the transform's space ends up in its parent's.
+    pub fn transform_point(&self, point: Vector3<f64>) -> Vector3<f64> {
+        let scaled = Vector3::new(point.x * self.scale.x,
+                                  point.y * self.scale.y,
+                                  point.z * self.scale.z);
+        self.rotation.rotate_vector(scaled) + self.translation
+    }
+
+    pub fn to_matrix(&self) -> Matrix4<f64> {
+        let scale = Matrix4::new(self.scale.x, 0.0, 0.0, 0.0,
+                                 0.0, self.scale.y, 0.0, 0.0,
+                                 0.0, 0.0, self.scale.z, 0.0,
+                                 0.0, 0.0, 0.0, 1.0);
+
+        Matrix4::from_translation(self.translation) * Matrix4::from(self.rotation) * scale
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Quaternion;
+    use cgmath::Rotation3;
+    use cgmath::Vector3;
+    use cgmath::rad;
+
+    use transform::Transform;
+
+    #[test]
+    fn slerp_takes_the_shortest_arc() {
+        let q = Quaternion::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), rad(1.0));
+        let from = Transform::identity().with_rotation(q);
+        let to = Transform::identity().with_rotation(-q);
+
+        let halfway = from.slerp(&to, 0.5).rotation;
+        assert!((halfway.dot(q).abs() - 1.0).abs() < 1e-9, "{:?} isn't {:?}", halfway, q);
+    }
+}
@@ -0,0 +1,54 @@
+use cgmath::Vector3;
+
+use rect::Point2i;
+use rect::Point3f;
+use rect::Rect;
+
+/// The rectangle of the render target that normalized device coordinates get mapped onto,
+/// along with the range that NDC depth is mapped into.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub min_depth: f64,
+    pub max_depth: f64,
+}
+
+impl Viewport {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Viewport {
+        Viewport {
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+
+    pub fn with_depth_range(self, min_depth: f64, max_depth: f64) -> Viewport {
+        Viewport { min_depth: min_depth, max_depth: max_depth, ..self }
+    }
+
+    pub fn aspect(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(Point2i::new(self.x, self.y),
+                  Point2i::new(self.x + self.width as i32, self.y + self.height as i32))
+    }
+
+    /// NDC (x and y in [-1, 1] with y up, z in [-1, 1]) to window coordinates (y down) and
+    /// depth in [min_depth, max_depth].
+    pub fn transform(&self, ndc: Vector3<f64>) -> Point3f {
+        let width = self.width as f64;
+        let height = self.height as f64;
+
+        Vector3::new(self.x as f64 + (ndc.x + 1.0) * 0.5 * width,
+                     self.y as f64 + (1.0 - ndc.y) * 0.5 * height,
+                     self.min_depth + (ndc.z + 1.0) * 0.5 * (self.max_depth - self.min_depth))
+    }
+}